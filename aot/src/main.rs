@@ -0,0 +1,96 @@
+// aot recompiles a CHIP-8 ROM into a Rust module that runs on
+// the machine crate, see machine::recompiler.
+//
+// usage: aot <rom> [output.rs]
+// the module is written to stdout if no output path is given.
+
+use std::process::ExitCode;
+use std::{env, fs};
+
+use machine::recompiler;
+
+// output of aot for a small counting loop, compiled to check the generated code
+#[cfg(test)]
+#[rustfmt::skip]
+mod sample;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (rom_path, output_path) = match args.as_slice() {
+        [rom] => (rom, None),
+        [rom, output] => (rom, Some(output)),
+        _ => {
+            eprintln!("usage: aot <rom> [output.rs]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let rom = match fs::read(rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", rom_path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let flow = recompiler::analyse(&rom, recompiler::LOAD_ADDRESS);
+    let source = recompiler::recompile(&rom, &flow);
+
+    match output_path {
+        Some(path) => {
+            if let Err(err) = fs::write(path, source) {
+                eprintln!("failed to write {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", source),
+    }
+
+    let code_bytes: usize = flow.blocks.values().map(|b| b.instructions.len() * 2).sum();
+    eprintln!(
+        "{}: {} blocks, {} subroutines, {} of {} bytes recompiled",
+        rom_path,
+        flow.blocks.len(),
+        flow.subroutines.len(),
+        code_bytes,
+        rom.len(),
+    );
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use machine::machine::Machine;
+    use machine::recompiler;
+
+    use super::sample;
+
+    #[test]
+    fn test_sample_is_current() {
+        let flow = recompiler::analyse(&sample::ROM, recompiler::LOAD_ADDRESS);
+        assert_eq!(
+            recompiler::recompile(&sample::ROM, &flow),
+            include_str!("sample.rs"),
+            "regenerate sample.rs with aot from its ROM bytes"
+        );
+    }
+
+    #[test]
+    fn test_sample_matches_interpreter() {
+        let mut native = Machine::with_seed(1);
+        sample::load(&mut native).unwrap();
+
+        let mut interpreted = Machine::with_seed(1);
+        interpreted.load_rom(&sample::ROM).unwrap();
+
+        for _ in 0..3 {
+            assert!(native.step_frame().unwrap());
+            assert!(interpreted.step_frame().unwrap());
+            assert_eq!(native.state_hash(), interpreted.state_hash());
+        }
+
+        assert_eq!(native.get_registers()[0], 8);
+        assert_eq!(native.get_cycles(), interpreted.get_cycles());
+    }
+}
@@ -0,0 +1,85 @@
+// Generated by the octochip recompiler, do not edit.
+// ROM size: 14 bytes
+// blocks: 5
+// subroutines: none
+
+#![allow(dead_code, unused_imports, clippy::all)]
+
+use machine::error::Error;
+use machine::instruction::Instruction;
+use machine::machine::Machine;
+use machine::machine::native::{Context, Exit};
+
+pub const LOAD_ADDRESS: u16 = 0x0200;
+
+pub static ROM: [u8; 14] = [
+    0x60, 0x05, 0x70, 0x01, 0x30, 0x08, 0x12, 0x02, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x0A,
+];
+
+// loads the ROM and attaches the native code to the machine
+pub fn load(machine: &mut Machine) -> Result<(), Error> {
+    machine.load_rom(&ROM)?;
+    machine.set_native(Some(run))
+}
+
+pub fn run(ctx: &mut Context<'_>) -> Exit {
+    loop {
+        let result = match ctx.pc() {
+            0x0200 => block_0200(ctx),
+            0x0202 => block_0202(ctx),
+            0x0206 => block_0206(ctx),
+            0x0208 => block_0208(ctx),
+            0x020A => block_020a(ctx),
+            _ => Err(Exit::Interpret),
+        };
+
+        if let Err(exit) = result {
+            return exit;
+        }
+    }
+}
+
+fn block_0200(ctx: &mut Context<'_>) -> Result<(), Exit> {
+    ctx.enter(0x0200, &[0x60, 0x05])?;
+    // 0x0200: 6005
+    ctx.set_v(0x0, 0x05);
+    ctx.jump(0x0202);
+    Ok(())
+}
+
+fn block_0202(ctx: &mut Context<'_>) -> Result<(), Exit> {
+    ctx.enter(0x0202, &[0x70, 0x01, 0x30, 0x08])?;
+    // 0x0202: 7001
+    ctx.set_v(0x0, ctx.v(0x0).wrapping_add(0x01));
+    // 0x0204: 3008
+    if ctx.v(0x0) == 0x08 {
+        ctx.jump(0x0208);
+    } else {
+        ctx.jump(0x0206);
+    }
+    Ok(())
+}
+
+fn block_0206(ctx: &mut Context<'_>) -> Result<(), Exit> {
+    ctx.enter(0x0206, &[0x12, 0x02])?;
+    // 0x0206: 1202
+    ctx.jump(0x0202);
+    Ok(())
+}
+
+fn block_0208(ctx: &mut Context<'_>) -> Result<(), Exit> {
+    ctx.enter(0x0208, &[0xF0, 0x29])?;
+    // 0x0208: F029
+    ctx.exec(0x0208, 1, Instruction::LoadFont(0))?;
+    ctx.jump(0x020A);
+    Ok(())
+}
+
+fn block_020a(ctx: &mut Context<'_>) -> Result<(), Exit> {
+    ctx.enter(0x020A, &[0xD0, 0x05, 0x12, 0x0A])?;
+    // 0x020A: D005
+    ctx.exec(0x020A, 2, Instruction::Draw { vx: 0, vy: 0, n: 5 })?;
+    // 0x020C: 120A
+    ctx.jump(0x020A);
+    Ok(())
+}
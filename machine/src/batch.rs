@@ -0,0 +1,387 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::error::Error;
+use crate::keyboard::Keyboard;
+use crate::machine::Machine;
+use crate::machine::snapshot::Snapshot;
+
+// Batch owns many copies of a machine and advances all of them one frame at
+// a time on a pool of worker threads. framebuffers of every instance are
+// copied into a single contiguous buffer after each frame, instance i
+// occupies framebuffers()[i * frame_size()..(i + 1) * frame_size()].
+pub struct Batch {
+    // instances split into one chunk per worker, moved to the
+    // worker for the duration of a frame and handed back after it
+    chunks: Vec<Chunk>,
+    chunk_size: usize,
+    len: usize,
+    pool: Option<Pool>,
+    framebuffers: Vec<u8>,
+    frame_size: usize,
+}
+
+struct Instance {
+    machine: Machine,
+    // error that stopped the instance, it is skipped until reset
+    error: Option<Error>,
+    // set once the machine halted or stopped, also skipped until reset
+    stopped: bool,
+}
+
+impl Instance {
+    fn is_running(&self) -> bool {
+        self.error.is_none() && !self.stopped
+    }
+
+    fn step_frame(&mut self, framebuffer: &mut [u8]) {
+        if !self.is_running() {
+            return;
+        }
+
+        match self.machine.step_frame() {
+            Ok(running) => self.stopped = !running,
+            Err(err) => self.error = Some(err),
+        }
+
+        self.copy_framebuffer(framebuffer);
+    }
+
+    fn copy_framebuffer(&self, framebuffer: &mut [u8]) {
+        let source = self.machine.get_display().framebuffer();
+        let len = source.len().min(framebuffer.len());
+        framebuffer[..len].copy_from_slice(&source[..len]);
+    }
+}
+
+#[derive(Default)]
+struct Chunk {
+    instances: Vec<Instance>,
+    // framebuffers of the chunk's instances, written by the worker
+    framebuffers: Vec<u8>,
+}
+
+impl Chunk {
+    fn step_frame(&mut self, frame_size: usize) {
+        for (instance, framebuffer) in self
+            .instances
+            .iter_mut()
+            .zip(self.framebuffers.chunks_mut(frame_size))
+        {
+            instance.step_frame(framebuffer);
+        }
+    }
+}
+
+// worker threads started by the first frame. each one waits for its
+// chunk, steps it by a frame and sends it back with its index.
+struct Pool {
+    jobs: Vec<Sender<Chunk>>,
+    done: Receiver<(usize, Chunk)>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    fn new(workers: usize, frame_size: usize) -> Self {
+        let (done_sender, done) = mpsc::channel();
+        let mut jobs = Vec::with_capacity(workers);
+        let mut handles = Vec::with_capacity(workers);
+
+        for index in 0..workers {
+            let (sender, receiver) = mpsc::channel::<Chunk>();
+            let done = done_sender.clone();
+
+            handles.push(thread::spawn(move || {
+                for mut chunk in receiver {
+                    chunk.step_frame(frame_size);
+                    if done.send((index, chunk)).is_err() {
+                        break;
+                    }
+                }
+            }));
+            jobs.push(sender);
+        }
+
+        Self {
+            jobs,
+            done,
+            handles,
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // closing the job channels ends the worker loops
+        self.jobs.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Batch {
+    // creates an instance per seed, each one a copy of the template machine
+    // with its own random generator. the template should have the ROM loaded.
+    pub fn new(template: &Machine, seeds: &[u64]) -> Self {
+        let frame_size = template.get_display().framebuffer().len();
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+
+        let instances: Vec<Instance> = seeds
+            .iter()
+            .map(|&seed| {
+                let mut machine = template.clone();
+                machine.reseed(seed);
+                Instance {
+                    machine,
+                    error: None,
+                    stopped: false,
+                }
+            })
+            .collect();
+
+        let mut batch = Self {
+            chunks: vec![Chunk::default()],
+            chunk_size: instances.len().max(1),
+            len: instances.len(),
+            pool: None,
+            framebuffers: vec![0; frame_size * instances.len()],
+            frame_size,
+        };
+        batch.chunks[0].instances = instances;
+        batch.split_chunks(workers);
+
+        for i in 0..batch.len {
+            batch.sync_framebuffer(i);
+        }
+
+        batch
+    }
+
+    // sets the number of worker threads, defaults to available parallelism
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.split_chunks(workers.max(1));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn machine(&self, i: usize) -> &Machine {
+        &self.instance(i).machine
+    }
+
+    // error that stopped the instance, if any
+    pub fn error(&self, i: usize) -> Option<&Error> {
+        self.instance(i).error.as_ref()
+    }
+
+    // true once the instance halted, stopped or failed
+    pub fn is_stopped(&self, i: usize) -> bool {
+        !self.instance(i).is_running()
+    }
+
+    pub fn set_keys(&mut self, i: usize, keys: Keyboard) {
+        self.instance_mut(i).machine.set_keys(keys);
+    }
+
+    // size in bytes of a single instance framebuffer
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    pub fn framebuffers(&self) -> &[u8] {
+        &self.framebuffers
+    }
+
+    pub fn framebuffer(&self, i: usize) -> &[u8] {
+        &self.framebuffers[i * self.frame_size..(i + 1) * self.frame_size]
+    }
+
+    // advances every running instance by one frame,
+    // returns the number of instances still running
+    pub fn step_frame(&mut self) -> usize {
+        if self.pool.is_none() && self.chunks.len() > 1 {
+            self.pool = Some(Pool::new(self.chunks.len(), self.frame_size));
+        }
+
+        match &self.pool {
+            Some(pool) => {
+                for (chunk, jobs) in self.chunks.iter_mut().zip(&pool.jobs) {
+                    jobs.send(std::mem::take(chunk))
+                        .expect("batch worker stopped");
+                }
+
+                for _ in 0..pool.jobs.len() {
+                    let (index, chunk) = pool.done.recv().expect("batch worker stopped");
+                    self.chunks[index] = chunk;
+                }
+            }
+            None => {
+                for chunk in &mut self.chunks {
+                    chunk.step_frame(self.frame_size);
+                }
+            }
+        }
+
+        let mut offset = 0;
+        for chunk in &self.chunks {
+            let end = offset + chunk.framebuffers.len();
+            self.framebuffers[offset..end].copy_from_slice(&chunk.framebuffers);
+            offset = end;
+        }
+
+        self.chunks
+            .iter()
+            .flat_map(|chunk| &chunk.instances)
+            .filter(|i| i.is_running())
+            .count()
+    }
+
+    // puts a single instance back into the snapshot state with a new seed,
+    // the ROM doesn't have to be loaded again
+    pub fn reset(&mut self, i: usize, snapshot: &Snapshot, seed: u64) {
+        let instance = self.instance_mut(i);
+        instance.machine.restore(snapshot);
+        instance.machine.reseed(seed);
+        instance.error = None;
+        instance.stopped = false;
+
+        self.sync_framebuffer(i);
+    }
+
+    fn instance(&self, i: usize) -> &Instance {
+        &self.chunks[i / self.chunk_size].instances[i % self.chunk_size]
+    }
+
+    fn instance_mut(&mut self, i: usize) -> &mut Instance {
+        &mut self.chunks[i / self.chunk_size].instances[i % self.chunk_size]
+    }
+
+    // regroups the instances into a chunk per worker, the pool
+    // is started with the matching size by the next frame
+    fn split_chunks(&mut self, workers: usize) {
+        self.pool = None;
+
+        let mut instances: Vec<Instance> = self
+            .chunks
+            .drain(..)
+            .flat_map(|chunk| chunk.instances)
+            .collect();
+
+        self.chunk_size = self.len.div_ceil(workers).max(1);
+        while !instances.is_empty() {
+            let rest = instances.split_off(self.chunk_size.min(instances.len()));
+            self.chunks.push(Chunk {
+                framebuffers: vec![0; instances.len() * self.frame_size],
+                instances,
+            });
+            instances = rest;
+        }
+    }
+
+    fn sync_framebuffer(&mut self, i: usize) {
+        let (chunk, j) = (i / self.chunk_size, i % self.chunk_size);
+        let chunk = &mut self.chunks[chunk];
+        let source = &mut chunk.framebuffers[j * self.frame_size..(j + 1) * self.frame_size];
+        chunk.instances[j].copy_framebuffer(source);
+
+        let range = i * self.frame_size..(i + 1) * self.frame_size;
+        self.framebuffers[range].copy_from_slice(source);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Batch;
+    use crate::instruction::Instruction::*;
+    use crate::keyboard::Keyboard;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    fn template() -> Machine {
+        // draws the digit of the pressed key, restarts when nothing is pressed
+        let program = Program(vec![
+            Clear,
+            SkipIfKey(0),
+            Jump(0x200),
+            LoadFont(0),
+            Draw { vx: 1, vy: 1, n: 5 },
+            Jump(0x208),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_step_frame() {
+        let template = template();
+        let snapshot = template.snapshot();
+
+        let mut batch = Batch::new(&template, &[1, 2, 3]).with_workers(2);
+        batch.set_keys(1, Keyboard::from(0b1));
+
+        assert_eq!(batch.step_frame(), 3);
+        assert_eq!(batch.framebuffers().len(), 3 * batch.frame_size());
+        assert!(batch.framebuffer(0).iter().all(|&b| b == 0));
+        assert!(batch.framebuffer(1).iter().any(|&b| b != 0));
+        assert!(batch.framebuffer(2).iter().all(|&b| b == 0));
+
+        batch.reset(1, &snapshot, 4);
+        assert!(batch.framebuffer(1).iter().all(|&b| b == 0));
+        assert_eq!(batch.machine(1).get_pc(), 0x200);
+    }
+
+    #[test]
+    fn test_workers_persist_across_frames() {
+        let seeds = [1, 2, 3, 4, 5];
+        let mut single = Batch::new(&template(), &seeds).with_workers(1);
+        let mut pooled = Batch::new(&template(), &seeds).with_workers(3);
+
+        for batch in [&mut single, &mut pooled] {
+            batch.set_keys(4, Keyboard::from(0b1));
+            for frame in 0..10 {
+                assert_eq!(batch.step_frame(), 5);
+                if frame == 0 {
+                    assert!(batch.framebuffer(4).iter().any(|&b| b != 0));
+                }
+            }
+        }
+
+        assert_eq!(pooled.len(), 5);
+        assert_eq!(single.framebuffers(), pooled.framebuffers());
+        assert!(pooled.framebuffer(3).iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_stopped_instances() {
+        let program = Program(vec![SkipIfKey(0), Jump(0x200), Stop]);
+
+        let mut template = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        template.load_program(program.into()).unwrap();
+        let snapshot = template.snapshot();
+
+        let mut batch = Batch::new(&template, &[1, 2, 3]).with_workers(2);
+        batch.set_keys(2, Keyboard::from(0b1));
+        assert_eq!(batch.step_frame(), 2);
+        assert!(batch.is_stopped(2));
+        assert!(batch.error(2).is_none());
+
+        // the stopped instance is no longer stepped
+        let pc = batch.machine(2).get_pc();
+        assert_eq!(batch.step_frame(), 2);
+        assert_eq!(batch.machine(2).get_pc(), pc);
+
+        batch.reset(2, &snapshot, 4);
+        assert!(!batch.is_stopped(2));
+        assert_eq!(batch.step_frame(), 3);
+    }
+}
@@ -0,0 +1,329 @@
+// Cheat codes applied to memory at frame boundaries, e.g. freezing the lives
+// counter found with search::Search. lists are shared as plain text:
+//
+//   # comment
+//   freeze 0x03F0 0x09 # infinite lives
+//   set 0x03F1 0x05 # start at level 5
+//   when 0x03F2 == 0x00 write 0x03F0 0x09
+//
+// every line is a code optionally followed by a description after `#`.
+// a leading `!` marks a disabled code.
+
+use crate::error::Error;
+use crate::memory::Memory;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Compare {
+    const ALL: [(&'static str, Compare); 6] = [
+        ("==", Compare::Equal),
+        ("!=", Compare::NotEqual),
+        ("<", Compare::Less),
+        ("<=", Compare::LessOrEqual),
+        (">", Compare::Greater),
+        (">=", Compare::GreaterOrEqual),
+    ];
+
+    fn matches(&self, lhs: u8, rhs: u8) -> bool {
+        match self {
+            Compare::Equal => lhs == rhs,
+            Compare::NotEqual => lhs != rhs,
+            Compare::Less => lhs < rhs,
+            Compare::LessOrEqual => lhs <= rhs,
+            Compare::Greater => lhs > rhs,
+            Compare::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        Self::ALL.iter().find(|(_, cmp)| cmp == self).unwrap().0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Code {
+    // writes the value on every frame
+    Freeze {
+        addr: u16,
+        value: u8,
+    },
+    // writes the value on the next frame only
+    Set {
+        addr: u16,
+        value: u8,
+    },
+    // writes the value on every frame mem[check] compares true against expected
+    When {
+        check: u16,
+        compare: Compare,
+        expected: u8,
+        addr: u16,
+        value: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: Code,
+    pub description: String,
+    pub enabled: bool,
+    // Set codes are applied once
+    applied: bool,
+}
+
+impl Cheat {
+    pub fn new(code: Code, description: &str) -> Self {
+        Self {
+            code,
+            description: description.to_string(),
+            enabled: true,
+            applied: false,
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let invalid = || Error::InvalidCheat(line.to_string());
+        let number = |value: &str| {
+            let value = value.strip_prefix("0x").unwrap_or(value);
+            u16::from_str_radix(value, 16).map_err(|_| invalid())
+        };
+        let byte = |value: &str| {
+            number(value).and_then(|value| u8::try_from(value).map_err(|_| invalid()))
+        };
+
+        let (code, description) = line.split_once('#').unwrap_or((line, ""));
+        let code = code.trim();
+        let (enabled, code) = match code.strip_prefix('!') {
+            Some(code) => (false, code),
+            None => (true, code),
+        };
+
+        let fields: Vec<&str> = code.split_whitespace().collect();
+        let code = match fields.as_slice() {
+            ["freeze", addr, value] => Code::Freeze {
+                addr: number(addr)?,
+                value: byte(value)?,
+            },
+            ["set", addr, value] => Code::Set {
+                addr: number(addr)?,
+                value: byte(value)?,
+            },
+            ["when", check, compare, expected, "write", addr, value] => Code::When {
+                check: number(check)?,
+                compare: Compare::ALL
+                    .iter()
+                    .find(|(symbol, _)| symbol == compare)
+                    .ok_or_else(invalid)?
+                    .1,
+                expected: byte(expected)?,
+                addr: number(addr)?,
+                value: byte(value)?,
+            },
+            _ => return Err(invalid()),
+        };
+
+        let mut cheat = Cheat::new(code, description.trim());
+        cheat.enabled = enabled;
+        Ok(cheat)
+    }
+
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+
+        if !self.enabled {
+            output.push('!');
+        }
+
+        match self.code {
+            Code::Freeze { addr, value } => {
+                output.push_str(&format!("freeze 0x{:04X} 0x{:02X}", addr, value))
+            }
+            Code::Set { addr, value } => {
+                output.push_str(&format!("set 0x{:04X} 0x{:02X}", addr, value))
+            }
+            Code::When {
+                check,
+                compare,
+                expected,
+                addr,
+                value,
+            } => output.push_str(&format!(
+                "when 0x{:04X} {} 0x{:02X} write 0x{:04X} 0x{:02X}",
+                check,
+                compare.symbol(),
+                expected,
+                addr,
+                value
+            )),
+        }
+
+        if !self.description.is_empty() {
+            output.push_str(&format!(" # {}", self.description));
+        }
+
+        output
+    }
+
+    // makes a Set code apply again on the next frame
+    pub fn rearm(&mut self) {
+        self.applied = false;
+    }
+
+    fn apply(&mut self, memory: &mut Memory) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        match self.code {
+            Code::Freeze { addr, value } => memory.write(addr.into(), value),
+            Code::Set { addr, value } => {
+                if self.applied {
+                    return Ok(());
+                }
+
+                self.applied = true;
+                memory.write(addr.into(), value)
+            }
+            Code::When {
+                check,
+                compare,
+                expected,
+                addr,
+                value,
+            } => {
+                if compare.matches(memory.read(check.into())?, expected) {
+                    memory.write(addr.into(), value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheatList(pub Vec<Cheat>);
+
+impl CheatList {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Cheat::parse)
+            .collect::<Result<Vec<_>>>()
+            .map(Self)
+    }
+
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+        for cheat in self.0.iter() {
+            output.push_str(&cheat.dump());
+            output.push('\n');
+        }
+
+        output
+    }
+
+    // applies all enabled codes in order, called by the machine
+    // at the start of every frame
+    pub fn apply(&mut self, memory: &mut Memory) -> Result<()> {
+        for cheat in self.0.iter_mut() {
+            cheat.apply(memory)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cheat, CheatList, Code, Compare};
+    use crate::instruction::Instruction::*;
+    use crate::keyboard::Keyboard;
+    use crate::machine::Machine;
+    use crate::program::Program;
+
+    const CHEATS: &str = "
+        # lives
+        freeze 0x0300 0x09 # infinite lives
+        !set 0x0301 0x05
+        set 0x0302 0x07
+        when 0x0303 >= 0x02 write 0x0304 0xFF # flag
+    ";
+
+    #[test]
+    fn test_parse_and_dump() {
+        let list = CheatList::parse(CHEATS).unwrap();
+        assert_eq!(list.0.len(), 4);
+        assert_eq!(list.0[0].description, "infinite lives");
+        assert!(!list.0[1].enabled);
+        assert_eq!(
+            list.0[3].code,
+            Code::When {
+                check: 0x303,
+                compare: Compare::GreaterOrEqual,
+                expected: 2,
+                addr: 0x304,
+                value: 0xFF
+            }
+        );
+
+        assert_eq!(CheatList::parse(&list.dump()).unwrap(), list);
+        assert!(Cheat::parse("freeze 0x300").is_err());
+        assert!(Cheat::parse("set 0x300 0x100").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        // loses a life at 0x300 and bumps counters at 0x302 and 0x303
+        // every time key E is pressed
+        let program = Program(vec![
+            SetIndex(0x300),
+            LoadRegisters(3),
+            AddImmediate { vx: 0, kk: 0xFF },
+            AddImmediate { vx: 2, kk: 1 },
+            AddImmediate { vx: 3, kk: 1 },
+            StoreRegisters(3),
+            WaitForKey(0xE),
+            Jump(0x202),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        *machine.cheats_mut() = CheatList::parse(CHEATS).unwrap();
+
+        machine.step_frame().unwrap();
+        let memory = machine.get_memory();
+        assert_eq!(memory.read(0x300).unwrap(), 0x08);
+        assert_eq!(memory.read(0x301).unwrap(), 0x00);
+        assert_eq!(memory.read(0x302).unwrap(), 0x08);
+
+        // frozen value is restored, set is not applied again
+        machine.step_frame().unwrap();
+        let memory = machine.get_memory();
+        assert_eq!(memory.read(0x300).unwrap(), 0x09);
+        assert_eq!(memory.read(0x302).unwrap(), 0x08);
+
+        machine.set_keys(Keyboard::from(1 << 0xE));
+        machine.step_frame().unwrap();
+        machine.set_keys(Keyboard::new());
+        assert_eq!(machine.get_memory().read(0x303).unwrap(), 0x02);
+        assert_eq!(machine.get_memory().read(0x304).unwrap(), 0x00);
+
+        machine.step_frame().unwrap();
+        assert_eq!(machine.get_memory().read(0x304).unwrap(), 0xFF);
+    }
+}
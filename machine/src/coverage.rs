@@ -0,0 +1,333 @@
+// Coverage map: marks every byte of memory as executed, read as data,
+// written or untouched while a ROM runs. attach it with
+// Machine::set_coverage.
+// exported as an annotated disassembly or a square image with one pixel
+// per byte of memory, 64x64 for the 4 KiB of a CHIP-8.
+
+use std::fmt::Write;
+
+use crate::checksum::{Crc32, adler32};
+use crate::instruction::Instruction;
+use crate::memory::Memory;
+
+#[derive(Clone)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub const EXECUTED: u8 = 0b001;
+    pub const READ: u8 = 0b010;
+    pub const WRITTEN: u8 = 0b100;
+
+    // covers `size` bytes, usually Machine::get_memory().size()
+    pub fn new(size: usize) -> Self {
+        Self {
+            flags: vec![0; size],
+        }
+    }
+
+    // marks memory used by an instruction executed at `pc`, `index` is
+    // the I register before the instruction ran and `sprite` the number
+    // of bytes a Draw reads, n or the size of a MegaChip sprite
+    pub fn record(&mut self, pc: u16, instruction: Instruction, index: u32, sprite: usize) {
+        use Instruction::*;
+
+        let length = match instruction {
+            LongIndex(_) => 4,
+            _ => 2,
+        };
+        self.mark(pc.into(), length, Self::EXECUTED);
+
+        match instruction {
+            Draw { .. } => self.mark(index, sprite, Self::READ),
+            LoadRegisters(x) => self.mark(index, x as usize + 1, Self::READ),
+            StoreRegisters(x) => self.mark(index, x as usize + 1, Self::WRITTEN),
+            StoreBcd(_) => self.mark(index, 3, Self::WRITTEN),
+            LoadRange { vx, vy } => self.mark(index, vx.abs_diff(vy) as usize + 1, Self::READ),
+            StoreRange { vx, vy } => self.mark(index, vx.abs_diff(vy) as usize + 1, Self::WRITTEN),
+            _ => (),
+        }
+    }
+
+    // bytes beyond the end of memory are not tracked
+    fn mark(&mut self, start: u32, len: usize, flag: u8) {
+        let start = start as usize;
+        let end = start.saturating_add(len).min(self.flags.len());
+        for flags in self.flags.get_mut(start..end).unwrap_or_default() {
+            *flags |= flag;
+        }
+    }
+
+    // combination of EXECUTED, READ and WRITTEN, 0 for untouched bytes
+    pub fn flags(&self, addr: u32) -> u8 {
+        self.flags.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    // number of bytes with any of the given flags set
+    pub fn count(&self, flag: u8) -> usize {
+        self.flags
+            .iter()
+            .filter(|&&flags| flags & flag != 0)
+            .count()
+    }
+
+    // disassembly of the whole memory, executed words are decoded,
+    // data bytes are listed one per line and untouched runs are collapsed
+    pub fn disassembly(&self, memory: &Memory) -> String {
+        let mut output = String::new();
+        let data = memory.as_slice();
+
+        let size = self.flags.len();
+        let mut addr = 0;
+        while addr < size {
+            let flags = self.flags[addr];
+
+            if flags == 0 {
+                let end = (addr..size).find(|&i| self.flags[i] != 0).unwrap_or(size);
+                writeln!(
+                    output,
+                    "{:04X}-{:04X}           ; untouched, {} bytes",
+                    addr,
+                    end - 1,
+                    end - addr
+                )
+                .unwrap();
+                addr = end;
+            } else if flags & Self::EXECUTED != 0 && addr + 1 < size {
+                let word = u16::from_be_bytes([data[addr], data[addr + 1]]);
+                let flags = flags | self.flags[addr + 1];
+                let text = match Instruction::decode(word) {
+                    Ok(instruction) => format!("{:?}", instruction),
+                    Err(_) => "invalid".to_string(),
+                };
+                writeln!(
+                    output,
+                    "{:04X}  {}  {:04X}  {}",
+                    addr,
+                    legend(flags),
+                    word,
+                    text
+                )
+                .unwrap();
+                addr += 2;
+            } else {
+                writeln!(
+                    output,
+                    "{:04X}  {}    {:02X}  data",
+                    addr,
+                    legend(flags),
+                    data[addr]
+                )
+                .unwrap();
+                addr += 1;
+            }
+        }
+
+        output
+    }
+
+    // binary PPM (P6) image
+    pub fn ppm(&self) -> Vec<u8> {
+        let side = self.image_side();
+        let mut output = format!("P6\n{} {}\n255\n", side, side).into_bytes();
+        for pixel in self.pixels() {
+            output.extend_from_slice(&pixel);
+        }
+        output
+    }
+
+    // 8-bit RGB PNG, the image data is stored without compression
+    pub fn png(&self) -> Vec<u8> {
+        let side = self.image_side();
+        let pixels = self.pixels();
+
+        let mut raw = Vec::with_capacity(side * (side * 3 + 1));
+        for row in pixels.chunks(side) {
+            raw.push(0); // no filter
+            for pixel in row {
+                raw.extend_from_slice(pixel);
+            }
+        }
+
+        // zlib stream made of stored deflate blocks
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(0xFFFF).peekable();
+        while let Some(block) = blocks.next() {
+            zlib.push(blocks.peek().is_none() as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(side as u32).to_be_bytes());
+        header.extend_from_slice(&(side as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB
+
+        let mut output = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut output, b"IHDR", &header);
+        png_chunk(&mut output, b"IDAT", &zlib);
+        png_chunk(&mut output, b"IEND", &[]);
+        output
+    }
+
+    // smallest square holding a pixel per byte of memory
+    fn image_side(&self) -> usize {
+        let mut side = (self.flags.len() as f64).sqrt() as usize;
+        while side * side < self.flags.len() {
+            side += 1;
+        }
+        side
+    }
+
+    // one pixel per byte, rows of image_side bytes. written, read and
+    // executed bytes light up the red, green and blue channel respectively,
+    // pixels past the end of memory are black
+    fn pixels(&self) -> Vec<[u8; 3]> {
+        let side = self.image_side();
+        let mut pixels: Vec<[u8; 3]> = self
+            .flags
+            .iter()
+            .map(|&flags| {
+                if flags == 0 {
+                    return [0x20, 0x20, 0x20];
+                }
+
+                let channel = |flag: u8| if flags & flag != 0 { 0xFF } else { 0x00 };
+                [
+                    channel(Self::WRITTEN),
+                    channel(Self::READ),
+                    channel(Self::EXECUTED),
+                ]
+            })
+            .collect();
+
+        pixels.resize(side * side, [0, 0, 0]);
+        pixels
+    }
+}
+
+fn legend(flags: u8) -> String {
+    [
+        (Coverage::EXECUTED, 'x'),
+        (Coverage::READ, 'r'),
+        (Coverage::WRITTEN, 'w'),
+    ]
+    .iter()
+    .map(|&(flag, c)| if flags & flag != 0 { c } else { '-' })
+    .collect()
+}
+
+fn png_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    output.extend_from_slice(&crc.finish().to_be_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::Coverage;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_coverage() {
+        let program = Program(vec![
+            SetIndex(0x300),               // 0x200
+            SetImmediate { vx: 0, kk: 7 }, // 0x202
+            StoreBcd(0),                   // 0x204
+            LoadRegisters(1),              // 0x206
+            Draw { vx: 0, vy: 0, n: 4 },   // 0x208
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        machine.set_coverage(Some(Coverage::new(machine.get_memory().size())));
+
+        for _ in 0..5 {
+            machine.step().unwrap();
+        }
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(coverage.count(Coverage::EXECUTED), 10);
+        assert_eq!(coverage.flags(0x209), Coverage::EXECUTED);
+        assert_eq!(coverage.flags(0x300), Coverage::READ | Coverage::WRITTEN);
+        assert_eq!(coverage.flags(0x302), Coverage::WRITTEN | Coverage::READ);
+        assert_eq!(coverage.flags(0x303), Coverage::READ);
+        assert_eq!(coverage.flags(0x304), 0);
+
+        let disassembly = coverage.disassembly(machine.get_memory());
+        assert!(disassembly.starts_with("0000-01FF           ; untouched, 512 bytes\n"));
+        assert!(disassembly.contains("0204  x--  F033  StoreBcd(0)\n"));
+        assert!(disassembly.contains("0301  -rw    00  data\n"));
+        assert!(disassembly.ends_with("0304-0FFF           ; untouched, 3324 bytes\n"));
+
+        let ppm = coverage.ppm();
+        assert!(ppm.starts_with(b"P6\n64 64\n255\n"));
+        assert_eq!(ppm.len(), 13 + 64 * 64 * 3);
+
+        let png = coverage.png();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+    }
+
+    #[test]
+    fn test_megachip_and_ranges() {
+        let program: Vec<u16> = [
+            MegaOn.into(),
+            SpriteWidth(3).into(),
+            SpriteHeight(2).into(),
+            SetIndex(0x300).into(),
+            Draw { vx: 0, vy: 0, n: 0 }.into(),
+            LongIndex(0x01).into(),
+            0x0000,
+            StoreRegisters(1).into(),
+        ]
+        .into();
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::MegaChip));
+        machine.load_program(program).unwrap();
+        machine.set_coverage(Some(Coverage::new(machine.get_memory().size())));
+        for _ in 0..7 {
+            machine.step().unwrap();
+        }
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(coverage.count(Coverage::READ), 6);
+        assert_eq!(coverage.flags(0x305), Coverage::READ);
+        assert_eq!(coverage.flags(0x20D), Coverage::EXECUTED);
+        assert_eq!(coverage.flags(0x10001), Coverage::WRITTEN);
+        let ppm = coverage.ppm();
+        assert!(ppm.starts_with(b"P6\n4096 4096\n255\n"));
+        assert_eq!(ppm.len(), 17 + 4096 * 4096 * 3);
+
+        let program = Program(vec![
+            SetIndex(0x300),
+            StoreRange { vx: 2, vy: 5 },
+            LoadRange { vx: 1, vy: 0 },
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_program(program.into()).unwrap();
+        machine.set_coverage(Some(Coverage::new(machine.get_memory().size())));
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(coverage.count(Coverage::WRITTEN), 4);
+        assert_eq!(coverage.flags(0x303), Coverage::WRITTEN);
+        // the store moved I past the stored registers
+        assert_eq!(coverage.flags(0x305), Coverage::READ);
+        assert_eq!(coverage.flags(0x306), 0);
+    }
+}
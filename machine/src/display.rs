@@ -1,23 +1,199 @@
-use crate::error::Error;
-use std::slice::Windows;
+use std::ops::{BitAnd, BitXorAssign, Not, Shl, Shr};
+
+// area of the display in pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u8,
+    pub y: u8,
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Rect {
+    // smallest rectangle containing both
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = (self.x as u16 + self.width as u16).max(other.x as u16 + other.width as u16);
+        let bottom = (self.y as u16 + self.height as u16).max(other.y as u16 + other.height as u16);
+
+        Rect {
+            x: left,
+            y: top,
+            width: (right - left as u16) as u8,
+            height: (bottom - top as u16) as u8,
+        }
+    }
+}
+
+// display size of a platform in pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Geometry {
+    pub const CHIP_8: Geometry = Geometry {
+        width: 64,
+        height: 32,
+    };
+
+    // the two-page display of the VIP hires interpreter
+    pub const HIRES: Geometry = Geometry {
+        width: 64,
+        height: 64,
+    };
+
+    pub const ETI_660: Geometry = Geometry {
+        width: 64,
+        height: 48,
+    };
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry::CHIP_8
+    }
+}
+
+// changed areas kept before they are merged into one
+const MAX_DIRTY_RECTS: usize = 16;
+
+// CHIP-8X colors of the VP-590 board: foreground 0 black, 1 red, 2 blue,
+// 3 violet, 4 green, 5 yellow, 6 aqua, 7 white and background 0 blue,
+// 1 black, 2 green, 3 red
+pub const FOREGROUND_COLORS: u8 = 8;
+pub const BACKGROUND_COLORS: u8 = 4;
+const DEFAULT_FOREGROUND: u8 = 1;
+// a display row as a machine word, the leftmost pixel is the most
+// significant bit. pixels past the display width are always zero.
+trait Row:
+    Copy
+    + Default
+    + PartialEq
+    + BitAnd<Output = Self>
+    + BitXorAssign
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const BITS: u32;
+
+    // a sprite byte over the 8 leftmost pixels
+    fn from_byte(byte: u8) -> Self;
+    fn leading_zeros(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    fn load(bytes: &[u8]) -> Self;
+    fn store(self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_row {
+    ($word:ty) => {
+        impl Row for $word {
+            const BITS: u32 = <$word>::BITS;
+
+            fn from_byte(byte: u8) -> Self {
+                (byte as $word) << (Self::BITS - 8)
+            }
+
+            fn leading_zeros(self) -> u32 {
+                <$word>::leading_zeros(self)
+            }
+
+            fn trailing_zeros(self) -> u32 {
+                <$word>::trailing_zeros(self)
+            }
+
+            fn load(bytes: &[u8]) -> Self {
+                let mut word = [0; size_of::<$word>()];
+                word[..bytes.len()].copy_from_slice(bytes);
+                <$word>::from_be_bytes(word)
+            }
+
+            fn store(self, bytes: &mut [u8]) {
+                let len = bytes.len();
+                bytes.copy_from_slice(&self.to_be_bytes()[..len]);
+            }
+        }
+    };
+}
+
+impl_row!(u64);
+impl_row!(u128);
+
+// the bits of a row that are on a display of the given width
+fn mask<R: Row>(width: u8) -> R {
+    !R::default() << (R::BITS - width as u32)
+}
+
+// u64 rows up to 64 pixels wide, u128 rows up to 128
+#[derive(Clone)]
+enum Rows {
+    Narrow(Vec<u64>),
+    Wide(Vec<u128>),
+}
 
+// calls $body with $rows bound to the row slice, whatever the row type
+macro_rules! with_rows {
+    ($display:expr, $rows:ident => $body:expr) => {
+        match &mut $display.rows {
+            Rows::Narrow($rows) => $body,
+            Rows::Wide($rows) => $body,
+        }
+    };
+}
+
+#[derive(Clone)]
 pub struct Display {
-    // row-major frame buffer
+    rows: Rows,
+    // the same pixels packed row-major, kept in sync with rows
     framebuffer: Vec<u8>,
 
     height: u8,
     width: u8,
+
+    // foreground color of each 8x1 pixel cell and the background color,
+    // only used by CHIP-8X
+    colors: Vec<u8>,
+    background: u8,
+
+    // incremented on every change of the framebuffer
+    generation: u64,
+    // areas changed since the last clear_dirty
+    dirty: Vec<Rect>,
 }
 
 impl Display {
     pub fn new() -> Self {
-        let height = 32;
-        let width = 64;
+        Self::with_geometry(Geometry::default())
+    }
+
+    pub fn with_geometry(geometry: Geometry) -> Self {
+        Self::with_size(geometry.width, geometry.height)
+    }
+
+    // width is a multiple of 8 up to 128
+    pub fn with_size(width: u8, height: u8) -> Self {
+        assert!(
+            width.is_multiple_of(8) && (8..=128).contains(&width),
+            "invalid display width {width}"
+        );
+
+        let rows = match width {
+            0..=64 => Rows::Narrow(vec![0; height as usize]),
+            _ => Rows::Wide(vec![0; height as usize]),
+        };
 
         Self {
-            framebuffer: Self::alloc_framebuffer(height, width),
+            rows,
+            framebuffer: vec![0; height as usize * width as usize / 8],
+            colors: vec![DEFAULT_FOREGROUND; height as usize * width as usize / 8],
+            background: 0,
             height,
             width,
+            generation: 0,
+            dirty: Vec::with_capacity(MAX_DIRTY_RECTS),
         }
     }
 
@@ -29,149 +205,482 @@ impl Display {
         self.width
     }
 
-    pub fn clear(&mut self) {
-        self.framebuffer = Self::alloc_framebuffer(self.height, self.width);
+    pub fn geometry(&self) -> Geometry {
+        Geometry {
+            width: self.width,
+            height: self.height,
+        }
     }
 
-    pub fn get_pixel(&self, x: u8, y: u8) -> bool {
-        if x >= self.width || y >= self.height {
-            return false;
+    // packed row-major framebuffer, 1 bit per pixel, MSB is the leftmost pixel
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    // areas changed since the last clear_dirty
+    pub fn dirty(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    // clears the dirty flag, the list keeps its storage for the next frame
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+    }
+
+    // replaces the picture with another display's one,
+    // the whole display is marked as changed
+    pub fn copy_from(&mut self, other: &Display) {
+        self.rows.clone_from(&other.rows);
+        self.framebuffer.clone_from(&other.framebuffer);
+        self.colors.clone_from(&other.colors);
+        self.background = other.background;
+        self.height = other.height;
+        self.width = other.width;
+        self.mark_all();
+    }
+
+    // sets 8 pixels of the packed framebuffer at once
+    pub fn write_byte(&mut self, index: usize, value: u8) {
+        let Some(byte) = self.framebuffer.get_mut(index) else {
+            return;
+        };
+
+        if *byte != value {
+            *byte = value;
+
+            let row_bytes = self.row_bytes();
+            let y = index / row_bytes;
+            let packed = &self.framebuffer[y * row_bytes..][..row_bytes];
+            with_rows!(self, rows => rows[y] = Row::load(packed));
+
+            self.mark(Rect {
+                x: (index % row_bytes * 8) as u8,
+                y: y as u8,
+                width: 8,
+                height: 1,
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if self.framebuffer.iter().any(|&byte| byte != 0) {
+            with_rows!(self, rows => rows.fill(0));
+            self.framebuffer.fill(0);
+            self.mark_all();
         }
+    }
 
-        let (byte_idx, bit_idx) = self.pixel_to_bit_offset(x, y);
-        let v = (self.framebuffer[byte_idx] >> bit_idx) & 1 == 1;
+    // moves the picture down by `rows`, rows scrolled in are blank
+    pub fn scroll_down(&mut self, rows: u8) {
+        let shift = rows.min(self.height) as usize;
+        with_rows!(self, rows => {
+            let len = rows.len();
+            rows.copy_within(..len - shift, shift);
+            rows[..shift].fill(0);
+        });
+        self.scrolled();
+    }
 
-        v
+    // moves the picture up by `rows`, rows scrolled in are blank
+    pub fn scroll_up(&mut self, rows: u8) {
+        let shift = rows.min(self.height) as usize;
+        with_rows!(self, rows => {
+            let len = rows.len();
+            rows.copy_within(shift.., 0);
+            rows[len - shift..].fill(0);
+        });
+        self.scrolled();
     }
 
-    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
-        let mut collision = false;
+    // moves the picture right by `columns`, columns scrolled in are blank
+    pub fn scroll_right(&mut self, columns: u8) {
+        let width = self.width;
+        with_rows!(self, rows => Self::shift_rows(rows, columns as i16, width));
+        self.scrolled();
+    }
 
-        for (row, sprite_byte) in sprite.iter().copied().enumerate() {
-            let y_pos = y + row as u8;
+    // moves the picture left by `columns`, columns scrolled in are blank
+    pub fn scroll_left(&mut self, columns: u8) {
+        let width = self.width;
+        with_rows!(self, rows => Self::shift_rows(rows, -(columns as i16), width));
+        self.scrolled();
+    }
 
-            // stop if sprite is out of screen
-            if y_pos > self.height {
-                break;
-            }
+    pub fn background(&self) -> u8 {
+        self.background
+    }
 
-            for bit in 0..8 {
-                let x_pos = x + bit;
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS;
+        self.mark_all();
+    }
 
-                // stop if sprite is out of screen
-                if x_pos > self.width {
-                    break;
-                }
+    // foreground color of the pixel
+    pub fn color(&self, x: u8, y: u8) -> u8 {
+        if x >= self.width || y >= self.height {
+            return DEFAULT_FOREGROUND;
+        }
 
-                let sprite_pixel = (sprite_byte >> (7 - bit)) & 1;
-                if sprite_pixel == 1 {
-                    if self.get_pixel(x_pos, y_pos) {
-                        collision = true
-                    }
+        self.colors[y as usize * self.row_bytes() + x as usize / 8]
+    }
 
-                    self.toggle_pixel(x_pos, y_pos);
-                }
-            }
+    // colors every 8x1 cell the area touches, the area is clipped
+    pub fn set_color(&mut self, area: Rect, color: u8) {
+        let right = (area.x as usize + area.width as usize).min(self.width as usize);
+        let bottom = (area.y as usize + area.height as usize).min(self.height as usize);
+        if area.x as usize >= right || area.y as usize >= bottom {
+            return;
         }
 
-        collision
+        let row_bytes = self.row_bytes();
+        let columns = area.x as usize / 8..right.div_ceil(8);
+        for y in area.y as usize..bottom {
+            self.colors[y * row_bytes..][columns.clone()].fill(color % FOREGROUND_COLORS);
+        }
+
+        let left = columns.start * 8;
+        self.mark(Rect {
+            x: left as u8,
+            y: area.y,
+            width: (columns.end * 8 - left) as u8,
+            height: (bottom - area.y as usize) as u8,
+        });
+    }
+
+    pub fn reset_colors(&mut self) {
+        self.colors.fill(DEFAULT_FOREGROUND);
+        self.background = 0;
+        self.mark_all();
     }
 
-    fn set_pixel(&mut self, x: u8, y: u8, value: bool) {
+    pub fn get_pixel(&self, x: u8, y: u8) -> bool {
         if x >= self.width || y >= self.height {
-            return;
+            return false;
         }
 
-        let (byte_idx, bit_idx) = self.pixel_to_bit_offset(x, y);
-        if value {
-            self.framebuffer[byte_idx] |= 1 << bit_idx;
-        } else {
-            self.framebuffer[byte_idx] &= !(1 << bit_idx);
+        let byte = self.framebuffer[y as usize * self.row_bytes() + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    // xors the sprite onto the display, pixels past the right and bottom
+    // edges are clipped. returns true if a lit pixel was turned off.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        let (width, row_bytes) = (self.width, self.row_bytes());
+        let framebuffer = &mut self.framebuffer;
+        let (collision, changed) = with_rows!(self, rows => {
+            Self::draw_rows(rows, framebuffer, width, row_bytes, x, y, sprite)
+        });
+
+        if let Some(rect) = changed {
+            self.mark(rect);
         }
+
+        collision
+    }
+
+    fn draw_rows<R: Row>(
+        rows: &mut [R],
+        framebuffer: &mut [u8],
+        width: u8,
+        row_bytes: usize,
+        x: u8,
+        y: u8,
+        sprite: &[u8],
+    ) -> (bool, Option<Rect>) {
+        let mask = mask::<R>(width);
+        let mut collision = false;
+        let mut changed: Option<Rect> = None;
+
+        for (y, &byte) in (y as usize..rows.len()).zip(sprite) {
+            let bits = (R::from_byte(byte) >> x as u32) & mask;
+            if bits == R::default() {
+                continue;
+            }
+
+            let row = &mut rows[y];
+            collision |= *row & bits != R::default();
+            *row ^= bits;
+            row.store(&mut framebuffer[y * row_bytes..][..row_bytes]);
+
+            let (left, right) = (bits.leading_zeros(), bits.trailing_zeros());
+            let rect = Rect {
+                x: left as u8,
+                y: y as u8,
+                width: (R::BITS - left - right) as u8,
+                height: 1,
+            };
+            changed = Some(changed.map_or(rect, |changed| changed.union(&rect)));
+        }
+
+        (collision, changed)
     }
 
-    fn toggle_pixel(&mut self, x: u8, y: u8) {
-        if x < self.width && y < self.height {
-            let pixel = self.get_pixel(x, y);
-            self.set_pixel(x, y, !pixel);
+    // positive columns shift right, negative ones left
+    fn shift_rows<R: Row>(rows: &mut [R], columns: i16, width: u8) {
+        let shift = columns.unsigned_abs() as u32;
+        for row in rows {
+            *row = match columns {
+                _ if shift >= width as u32 => R::default(),
+                0.. => (*row >> shift) & mask(width),
+                _ => *row << shift,
+            };
         }
     }
 
-    fn pixel_to_bit_offset(&self, x: u8, y: u8) -> (usize, usize) {
-        // in row-major, new row starts at byte y*N,
-        // where N is count of bytes in a row what is equal to number of
-        // columns divided by 8 as each column takes only 1 bit.
+    // rewrites the packed framebuffer after the rows moved
+    fn scrolled(&mut self) {
+        let row_bytes = self.row_bytes();
+        let packed = self.framebuffer.chunks_exact_mut(row_bytes);
+        with_rows!(self, rows => for (row, bytes) in rows.iter().zip(packed) {
+            row.store(bytes);
+        });
+        self.mark_all();
+    }
 
-        let offset = y as u16 * self.width as u16 / 8;
-        let byte_index = x as u16 / 8 + offset;
-        let bit_index = 7 - x % 8;
+    fn row_bytes(&self) -> usize {
+        self.width as usize / 8
+    }
 
-        (byte_index as usize, bit_index as usize)
+    fn mark_all(&mut self) {
+        self.mark(Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        });
     }
 
-    fn alloc_framebuffer(height: u8, width: u8) -> Vec<u8> {
-        let mut framebuffer = Vec::new();
-        let buffer_size = height as u16 * width as u16 / 8;
-        for _ in 0..buffer_size {
-            framebuffer.push(0);
+    fn mark(&mut self, rect: Rect) {
+        self.generation += 1;
+
+        if self.dirty.len() == MAX_DIRTY_RECTS {
+            let bounds = self.dirty.iter().fold(rect, |acc, r| acc.union(r));
+            self.dirty.clear();
+            self.dirty.push(bounds);
+        } else {
+            self.dirty.push(rect);
         }
+    }
+}
 
-        framebuffer
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Display;
+    use super::{Display, Rect};
 
     #[test]
-    fn test_pixel_to_bit_offset() {
-        let dsp = Display::new();
+    fn test_write_byte() {
+        let mut dsp = Display::new();
+
+        dsp.write_byte(0, 0b10000000);
+        dsp.write_byte(80, 0b00000100);
+        dsp.write_byte(84, 0b01000000);
 
-        let (byte_idx, bit_idx) = dsp.pixel_to_bit_offset(11, 7);
-        assert_eq!(byte_idx, 57);
-        assert_eq!(bit_idx, 4);
+        assert!(dsp.get_pixel(0, 0));
+        assert!(dsp.get_pixel(5, 10));
+        assert!(dsp.get_pixel(33, 10));
+        assert!(!dsp.get_pixel(34, 10));
+
+        // rows follow the packed framebuffer
+        assert!(dsp.draw_sprite(32, 10, &[0x40]));
+        assert_eq!(dsp.framebuffer()[84], 0);
     }
 
     #[test]
-    fn test_set_pixel() {
+    fn test_draw_sprite() {
         let mut dsp = Display::new();
 
-        dsp.set_pixel(0, 0, true);
-        assert_eq!(dsp.framebuffer[0], 0b10000000);
-
-        dsp.set_pixel(5, 10, true);
-        assert_eq!(dsp.framebuffer[80], 0b00000100);
+        assert!(!dsp.draw_sprite(7, 17, &[0x80]));
+        assert!(dsp.get_pixel(7, 17));
+        assert_eq!(dsp.framebuffer()[17 * 8], 0x01);
 
-        dsp.set_pixel(33, 10, true);
-        assert_eq!(dsp.framebuffer[84], 0b01000000);
+        assert!(dsp.draw_sprite(7, 17, &[0x80]));
+        assert!(!dsp.get_pixel(7, 17));
     }
 
     #[test]
-    fn test_get_pixel() {
+    fn test_clipping() {
         let mut dsp = Display::new();
-        dsp.framebuffer[0] = 0b10000000;
-        dsp.framebuffer[80] = 0b00000100;
-        dsp.framebuffer[84] = 0b01000000;
 
-        let pixel = dsp.get_pixel(0, 0);
-        assert!(pixel);
-
-        let pixel = dsp.get_pixel(5, 10);
-        assert!(pixel);
+        // x + bit and y + row used to overflow u8
+        assert!(!dsp.draw_sprite(252, 252, &[0xFF; 8]));
+        assert!(!dsp.is_dirty());
+
+        dsp.draw_sprite(60, 30, &[0xFF, 0xFF, 0xFF]);
+        assert_eq!(
+            dsp.framebuffer()
+                .iter()
+                .map(|b| b.count_ones())
+                .sum::<u32>(),
+            8
+        );
+        assert!(dsp.get_pixel(63, 31));
+        assert_eq!(
+            dsp.dirty(),
+            [Rect {
+                x: 60,
+                y: 30,
+                width: 4,
+                height: 2
+            }]
+        );
+
+        let mut wide = Display::with_size(128, 64);
+        wide.draw_sprite(124, 63, &[0xFF, 0xFF]);
+        assert!(wide.get_pixel(127, 63));
+        assert_eq!(wide.framebuffer()[wide.framebuffer().len() - 1], 0x0F);
+    }
 
-        let pixel = dsp.get_pixel(33, 10);
-        assert!(pixel);
+    #[test]
+    fn test_dirty_tracking() {
+        let mut dsp = Display::new();
+        assert!(!dsp.is_dirty());
+
+        dsp.draw_sprite(4, 2, &[0x00, 0x18, 0x00]);
+        dsp.draw_sprite(60, 30, &[0x80]);
+        assert!(dsp.is_dirty());
+        assert_eq!(dsp.generation(), 2);
+        assert_eq!(
+            dsp.dirty(),
+            [
+                Rect {
+                    x: 7,
+                    y: 3,
+                    width: 2,
+                    height: 1
+                },
+                Rect {
+                    x: 60,
+                    y: 30,
+                    width: 1,
+                    height: 1
+                },
+            ]
+        );
+        dsp.clear_dirty();
+        assert!(!dsp.is_dirty());
+
+        // nothing drawn, nothing changed
+        dsp.draw_sprite(0, 0, &[0x00]);
+        dsp.write_byte(0, 0x00);
+        assert!(!dsp.is_dirty());
+
+        // past the limit, changed areas are merged into their bounding box
+        for i in 0..20 {
+            dsp.draw_sprite(i, i, &[0x80]);
+        }
+        let dirty = dsp.dirty();
+        assert_eq!(dirty.len(), 4);
+        assert_eq!(
+            dirty[0],
+            Rect {
+                x: 0,
+                y: 0,
+                width: 17,
+                height: 17
+            }
+        );
+
+        dsp.clear_dirty();
+        dsp.clear();
+        dsp.clear();
+        assert_eq!(dsp.dirty().len(), 1);
+        assert_eq!(dsp.generation(), 23);
+
+        // the list is reused from frame to frame
+        let storage = dsp.dirty().as_ptr();
+        for i in 0..20 {
+            dsp.clear_dirty();
+            dsp.draw_sprite(i, 0, &[0x80]);
+            assert_eq!(dsp.dirty().as_ptr(), storage);
+        }
     }
 
     #[test]
-    fn test_toggle_pixel() {
+    fn test_scroll() {
         let mut dsp = Display::new();
+        dsp.draw_sprite(0, 0, &[0x80]);
+        dsp.draw_sprite(63, 31, &[0x80]);
+
+        dsp.scroll_down(1);
+        assert!(dsp.get_pixel(0, 1));
+        assert!(!dsp.get_pixel(63, 31));
+
+        dsp.scroll_right(4);
+        assert!(dsp.get_pixel(4, 1));
+
+        dsp.scroll_left(2);
+        dsp.scroll_up(1);
+        assert!(dsp.get_pixel(2, 0));
+        assert_eq!(
+            dsp.framebuffer()
+                .iter()
+                .map(|b| b.count_ones())
+                .sum::<u32>(),
+            1
+        );
+        assert!(dsp.is_dirty());
+
+        let mut wide = Display::with_size(128, 64);
+        wide.draw_sprite(120, 0, &[0x01]);
+        wide.scroll_right(4);
+        assert_eq!(wide.framebuffer()[15], 0);
+        wide.draw_sprite(0, 0, &[0x80]);
+        wide.scroll_left(1);
+        assert_eq!(wide.framebuffer()[0], 0);
+    }
 
-        dsp.toggle_pixel(7, 17);
-        assert_eq!(dsp.get_pixel(7, 17), true);
+    #[test]
+    fn test_colors() {
+        let mut dsp = Display::new();
+        assert_eq!(dsp.color(0, 0), 1);
+
+        // cells are 8 pixels wide, partially covered ones are colored too
+        let area = Rect {
+            x: 12,
+            y: 4,
+            width: 8,
+            height: 2,
+        };
+        dsp.set_color(area, 4);
+        assert_eq!(dsp.color(7, 4), 1);
+        assert_eq!(dsp.color(8, 4), 4);
+        assert_eq!(dsp.color(23, 5), 4);
+        assert_eq!(dsp.color(24, 5), 1);
+        assert_eq!(dsp.color(8, 6), 1);
+        assert_eq!(
+            dsp.dirty(),
+            [Rect {
+                x: 8,
+                y: 4,
+                width: 16,
+                height: 2
+            }]
+        );
+
+        for _ in 0..5 {
+            dsp.cycle_background();
+        }
+        assert_eq!(dsp.background(), 1);
 
-        dsp.toggle_pixel(7, 17);
-        assert_eq!(dsp.get_pixel(7, 17), false);
+        dsp.reset_colors();
+        assert_eq!((dsp.color(8, 4), dsp.background()), (1, 0));
     }
 }
@@ -0,0 +1,208 @@
+// Reinforcement learning environment on top of Machine: a fixed ROM state
+// to start episodes from, discrete actions mapped to key presses and
+// rewards/termination described with expressions over machine state.
+
+use crate::error::Error;
+use crate::keyboard::Keyboard;
+use crate::machine::Machine;
+use crate::machine::snapshot::Snapshot;
+
+mod expr;
+
+pub use expr::{BinaryOp, Expr};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// ActionSpace maps discrete action indices to keyboard states
+#[derive(Clone)]
+pub struct ActionSpace(Vec<Keyboard>);
+
+impl ActionSpace {
+    pub fn new(actions: Vec<Keyboard>) -> Self {
+        Self(actions)
+    }
+
+    // action 0 presses nothing, action i presses keys[i - 1]
+    pub fn from_keys(keys: &[u8]) -> Self {
+        let mut actions = vec![Keyboard::new()];
+        for &key in keys {
+            let mut keyboard = Keyboard::new();
+            keyboard.set_key(key, true);
+            actions.push(keyboard);
+        }
+
+        Self(actions)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, action: usize) -> Option<Keyboard> {
+        self.0.get(action).copied()
+    }
+}
+
+#[derive(Clone)]
+pub enum Reward {
+    // change of the value since the previous step, e.g. a score counter
+    Delta(Expr),
+    // value evaluated after every frame and summed over skipped frames
+    Value(Expr),
+}
+
+pub struct Step<'a> {
+    // packed 1 bit per pixel framebuffer, see Display::framebuffer
+    pub observation: &'a [u8],
+    pub reward: i64,
+    pub done: bool,
+}
+
+pub struct Environment {
+    machine: Machine,
+    initial: Snapshot,
+    actions: ActionSpace,
+
+    reward: Reward,
+    done: Expr,
+    frame_skip: u32,
+
+    // last value of a Delta reward
+    reward_value: i64,
+}
+
+impl Environment {
+    // episodes start from the current state of the machine,
+    // which is expected to have the ROM loaded
+    pub fn new(machine: Machine, actions: ActionSpace) -> Self {
+        Self {
+            initial: machine.snapshot(),
+            machine,
+            actions,
+            reward: Reward::Value(Expr::Const(0)),
+            done: Expr::Const(0),
+            frame_skip: 1,
+            reward_value: 0,
+        }
+    }
+
+    pub fn with_reward(mut self, reward: Reward) -> Self {
+        self.reward = reward;
+        self
+    }
+
+    pub fn with_done(mut self, done: Expr) -> Self {
+        self.done = done;
+        self
+    }
+
+    // number of frames every action is repeated for
+    pub fn with_frame_skip(mut self, frames: u32) -> Self {
+        self.frame_skip = frames.max(1);
+        self
+    }
+
+    pub fn actions(&self) -> &ActionSpace {
+        &self.actions
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    // starts a new episode and returns the initial observation
+    pub fn reset(&mut self, seed: u64) -> &[u8] {
+        self.machine.restore(&self.initial);
+        self.machine.reseed(seed);
+
+        if let Reward::Delta(expr) = &self.reward {
+            self.reward_value = expr.eval(&self.machine);
+        }
+
+        self.machine.get_display().framebuffer()
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Step<'_>> {
+        let keys = self
+            .actions
+            .get(action)
+            .ok_or(Error::InvalidAction(action))?;
+        self.machine.set_keys(keys);
+
+        let mut reward = 0;
+        let mut done = false;
+
+        for _ in 0..self.frame_skip {
+            done = !self.machine.step_frame()?;
+
+            reward += match &self.reward {
+                Reward::Delta(expr) => {
+                    let value = expr.eval(&self.machine);
+                    let delta = value - self.reward_value;
+                    self.reward_value = value;
+                    delta
+                }
+                Reward::Value(expr) => expr.eval(&self.machine),
+            };
+
+            done |= self.done.eval(&self.machine) != 0;
+            if done {
+                break;
+            }
+        }
+
+        Ok(Step {
+            observation: self.machine.get_display().framebuffer(),
+            reward,
+            done,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ActionSpace, Environment, Expr, Reward};
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::program::Program;
+
+    #[test]
+    fn test_episode() {
+        // V1 counts presses of key 5 as the score
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 5 },
+            WaitForKey(3),
+            SkipIfNotEqual { vx: 0, vy: 3 },
+            AddImmediate { vx: 1, kk: 1 },
+            Jump(0x202),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+
+        let mut env = Environment::new(machine, ActionSpace::from_keys(&[5, 6]))
+            .with_reward(Reward::Delta(Expr::parse("V1").unwrap()))
+            .with_done(Expr::parse("V1 >= 4").unwrap());
+
+        env.reset(1);
+        assert_eq!(env.actions().len(), 3);
+
+        let step = env.step(0).unwrap();
+        assert_eq!((step.reward, step.done), (0, false));
+
+        // a frame is long enough for the loop to run through twice
+        let step = env.step(1).unwrap();
+        assert_eq!((step.reward, step.done), (2, false));
+
+        let step = env.step(1).unwrap();
+        assert!(step.done);
+
+        env.reset(2);
+        assert_eq!(env.machine().get_registers()[1], 0);
+        assert!(env.step(3).is_err());
+    }
+}
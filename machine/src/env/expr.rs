@@ -0,0 +1,313 @@
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::machine::Machine;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Expr is a declarative expression over machine state used to describe
+// rewards and episode termination, e.g. "mem[0x3F0]" or "V5 == 0".
+//
+// operands: decimal or 0x-prefixed hex numbers, V0..VF, I, PC, DT, ST
+// and mem[expr] for a memory byte. operators, from lowest precedence:
+// ||, &&, == != < <= > >=, | ^ &, + -, *, unary ! and -.
+// comparisons and logical operators evaluate to 0 or 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    Register(u8),
+    Index,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Subtract,
+    Multiply,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+
+        let expr = parser.parse_binary(0)?;
+        if parser.pos != parser.tokens.len() {
+            return Err(Error::InvalidExpression(source.to_string()));
+        }
+
+        Ok(expr)
+    }
+
+    // evaluates the expression, reading memory out of bounds yields 0
+    pub fn eval(&self, machine: &Machine) -> i64 {
+        use BinaryOp::*;
+
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Register(x) => machine.get_registers()[*x as usize] as i64,
+            Expr::Index => machine.get_index() as i64,
+            Expr::ProgramCounter => machine.get_pc() as i64,
+            Expr::DelayTimer => machine.get_delay_timer() as i64,
+            Expr::SoundTimer => machine.get_sound_timer() as i64,
+            Expr::Memory(addr) => {
+                let addr = addr.eval(machine);
+                u32::try_from(addr)
+                    .ok()
+                    .and_then(|addr| machine.get_memory().read(addr).ok())
+                    .unwrap_or(0) as i64
+            }
+            Expr::Not(expr) => (expr.eval(machine) == 0) as i64,
+            Expr::Negate(expr) => expr.eval(machine).wrapping_neg(),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(machine);
+
+                // short circuit logical operators
+                match op {
+                    Or if lhs != 0 => return 1,
+                    And if lhs == 0 => return 0,
+                    _ => {}
+                }
+
+                let rhs = rhs.eval(machine);
+                match op {
+                    Or | And => (rhs != 0) as i64,
+                    Equal => (lhs == rhs) as i64,
+                    NotEqual => (lhs != rhs) as i64,
+                    Less => (lhs < rhs) as i64,
+                    LessOrEqual => (lhs <= rhs) as i64,
+                    Greater => (lhs > rhs) as i64,
+                    GreaterOrEqual => (lhs >= rhs) as i64,
+                    BitOr => lhs | rhs,
+                    BitXor => lhs ^ rhs,
+                    BitAnd => lhs & rhs,
+                    Add => lhs.wrapping_add(rhs),
+                    Subtract => lhs.wrapping_sub(rhs),
+                    Multiply => lhs.wrapping_mul(rhs),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 19] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "*", "!", "(", ")", "[",
+    "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let invalid = || Error::InvalidExpression(source.to_string());
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+
+        if c.is_ascii_alphanumeric() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+
+            let token = if c.is_ascii_digit() {
+                let number = match word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                Token::Number(number.map_err(|_| invalid())?)
+            } else {
+                Token::Ident(word.to_ascii_uppercase())
+            };
+
+            tokens.push(token);
+            rest = &rest[len..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(invalid)?;
+
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+// binary operators grouped by precedence, lowest first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 7] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Equal),
+        ("!=", BinaryOp::NotEqual),
+        ("<", BinaryOp::Less),
+        ("<=", BinaryOp::LessOrEqual),
+        (">", BinaryOp::Greater),
+        (">=", BinaryOp::GreaterOrEqual),
+    ],
+    &[("|", BinaryOp::BitOr), ("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+    &[("*", BinaryOp::Multiply)],
+];
+
+impl Parser {
+    fn parse_binary(&mut self, level: usize) -> Result<Expr> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut lhs = self.parse_binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let Some(&(_, bin_op)) = PRECEDENCE[level].iter().find(|(s, _)| s == op) else {
+                break;
+            };
+
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1)?;
+            lhs = Expr::Binary(bin_op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        let token = self.next()?;
+
+        let expr = match token {
+            Token::Number(value) => Expr::Const(value),
+            Token::Op("!") => Expr::Not(Box::new(self.parse_unary()?)),
+            Token::Op("-") => Expr::Negate(Box::new(self.parse_unary()?)),
+            Token::Op("(") => {
+                let expr = self.parse_binary(0)?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Ident(name) => match name.as_str() {
+                "I" => Expr::Index,
+                "PC" => Expr::ProgramCounter,
+                "DT" => Expr::DelayTimer,
+                "ST" => Expr::SoundTimer,
+                "MEM" => {
+                    self.expect("[")?;
+                    let addr = self.parse_binary(0)?;
+                    self.expect("]")?;
+                    Expr::Memory(Box::new(addr))
+                }
+                _ => match name.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+                    Some(Ok(x)) if x <= 0xF && name.len() == 2 => Expr::Register(x),
+                    _ => return Err(Error::InvalidExpression(name)),
+                },
+            },
+            Token::Op(op) => return Err(Error::InvalidExpression(op.to_string())),
+        };
+
+        Ok(expr)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| Error::InvalidExpression("unexpected end".to_string()))?;
+
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        match self.next()? {
+            Token::Op(got) if got == op => Ok(()),
+            _ => Err(Error::InvalidExpression(format!("expected '{}'", op))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Expr;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::program::Program;
+
+    #[test]
+    fn test_eval() {
+        let program = Program(vec![
+            SetImmediate { vx: 5, kk: 3 },
+            SetIndex(0x300),
+            StoreRegisters(5),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        let table = [
+            ("V5", 3),
+            ("v5 == 3 && I == 0x300", 1),
+            ("mem[0x305] * 10 + 1", 31),
+            ("mem[I + 5] - 4 < 0", 1),
+            ("!(V5 != 3) || 0", 1),
+            ("VF | 2 ^ 7", 5),
+            ("1 + 2 * 3", 7),
+            ("mem[0x10000]", 0),
+        ];
+
+        for (source, want) in table {
+            let expr = Expr::parse(source).unwrap();
+            assert_eq!(expr.eval(&machine), want, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in ["", "V5 ==", "VG", "mem[1", "1 2", "V5 $ 2"] {
+            assert!(Expr::parse(source).is_err(), "{}", source);
+        }
+    }
+}
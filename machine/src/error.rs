@@ -1,17 +1,251 @@
+use std::fmt;
+
 use crate::instruction::Instruction;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Error {
-    MemoryOutOfBound,
+    MemoryOutOfBound(u32),
     InvalidInstruction(u16),
     NotImplementedYet(Instruction),
     StackUnderflow,
     StackOverflow,
 
-    InvalidIndexAddress(u16),
-    IndexOverflow(u16),
+    InvalidIndexAddress(u32),
+    IndexOverflow(u32),
     InvalidProgramCounter(u16),
     UnalignedProgramCounter(u16),
 
     InvalidKeyIndex(u8),
+
+    InvalidExpression(String),
+    InvalidAction(usize),
+
+    InvalidCheat(String),
+    InvalidFont(String),
+    InvalidPalette(usize),
+    InvalidMemoryMap(String),
+    PlaneMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidInterpreter(usize),
+    InvalidNative(String),
+    MachineCodeTimeout(u16),
+    BufferTooSmall {
+        expected: usize,
+        actual: usize,
+    },
+    MemorySizeChanged {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidPatch(String),
+    AccessVetoed {
+        addr: u32,
+        pc: u16,
+    },
+    PatchChecksumMismatch {
+        kind: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+    InvalidMovie(String),
+    RomHashMismatch(u32),
+    MovieDesync {
+        frame: usize,
+        expected: u32,
+        actual: u32,
+    },
+
+    // an error raised by an instruction, with the machine state at that point
+    Fault(Box<Fault>),
+}
+
+// execution context of a failed Machine::step
+#[derive(Debug, Clone)]
+pub struct Fault {
+    pub error: Error,
+    // address of the failing instruction
+    pub pc: u16,
+    // None if the instruction could not be fetched
+    pub opcode: Option<u16>,
+    // None if the instruction could not be fetched or decoded
+    pub instruction: Option<Instruction>,
+    // instructions executed since the program was loaded
+    pub cycles: u64,
+    // return addresses of active subroutines, outermost first
+    pub call_stack: Vec<u16>,
+}
+
+impl Error {
+    // the underlying error, without the execution context of a fault
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::Fault(fault) => fault.error.cause(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MemoryOutOfBound(addr) => {
+                write!(f, "memory access out of bounds at {:#05X}", addr)
+            }
+            Error::InvalidInstruction(opcode) => write!(f, "invalid instruction {:04X}", opcode),
+            Error::NotImplementedYet(instruction) => {
+                write!(f, "instruction {:?} is not implemented", instruction)
+            }
+            Error::StackUnderflow => write!(f, "return with an empty stack"),
+            Error::StackOverflow => write!(f, "call with a full stack"),
+            Error::InvalidIndexAddress(addr) => {
+                write!(f, "index {:#05X} points below the program area", addr)
+            }
+            Error::IndexOverflow(addr) => write!(f, "index {:#05X} is beyond memory", addr),
+            Error::InvalidProgramCounter(pc) => {
+                write!(f, "program counter {:#05X} is outside the program area", pc)
+            }
+            Error::UnalignedProgramCounter(pc) => {
+                write!(f, "program counter {:#05X} is not aligned", pc)
+            }
+            Error::InvalidKeyIndex(key) => write!(f, "invalid key {:#X}", key),
+            Error::InvalidExpression(expr) => write!(f, "invalid expression: {}", expr),
+            Error::InvalidAction(action) => write!(f, "invalid action {}", action),
+            Error::InvalidCheat(line) => write!(f, "invalid cheat: {}", line),
+            Error::InvalidFont(reason) => write!(f, "invalid font: {}", reason),
+            Error::InvalidPalette(colors) => {
+                write!(f, "palette of {} colors, 2, 4, 8 or 16 are needed", colors)
+            }
+            Error::InvalidMemoryMap(reason) => write!(f, "invalid memory map: {}", reason),
+            Error::PlaneMismatch { expected, actual } => write!(
+                f,
+                "{} planes given for a palette of {} planes",
+                actual, expected
+            ),
+            Error::InvalidInterpreter(size) => {
+                write!(f, "interpreter of {} bytes overlaps the program", size)
+            }
+            Error::InvalidNative(reason) => write!(f, "invalid native program: {}", reason),
+            Error::MachineCodeTimeout(addr) => {
+                write!(f, "machine code at {:#05X} did not return", addr)
+            }
+            Error::BufferTooSmall { expected, actual } => write!(
+                f,
+                "buffer holds {} elements, {} are needed",
+                actual, expected
+            ),
+            Error::MemorySizeChanged { expected, actual } => write!(
+                f,
+                "memory of {} bytes does not match the snapshot of {} bytes",
+                actual, expected
+            ),
+            Error::InvalidPatch(reason) => write!(f, "invalid patch: {}", reason),
+            Error::AccessVetoed { addr, pc } => {
+                write!(f, "access to {:#05X} vetoed by a hook at {:#05X}", addr, pc)
+            }
+            Error::PatchChecksumMismatch {
+                kind,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} checksum mismatch: expected {:08X}, got {:08X}",
+                kind, expected, actual
+            ),
+            Error::InvalidMovie(line) => write!(f, "invalid movie: {}", line),
+            Error::RomHashMismatch(hash) => {
+                write!(f, "movie was recorded for a ROM with CRC-32 {:08X}", hash)
+            }
+            Error::MovieDesync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "movie desync at frame {}: expected state {:08X}, got {:08X}",
+                frame, expected, actual
+            ),
+            Error::Fault(fault) => fault.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the error itself is the source of Error::Fault
+        write!(f, "fault at {:#05X}", self.pc)?;
+
+        match (self.opcode, self.instruction) {
+            (Some(opcode), Some(instruction)) => write!(f, " ({:04X} {:?})", opcode, instruction)?,
+            (Some(opcode), None) => write!(f, " ({:04X})", opcode)?,
+            _ => (),
+        }
+
+        write!(f, " after {} cycles", self.cycles)?;
+
+        if !self.call_stack.is_empty() {
+            write!(f, ", call stack:")?;
+            for addr in &self.call_stack {
+                write!(f, " {:#05X}", addr)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Fault(fault) => Some(&fault.error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_fault() {
+        let program = Program(vec![
+            Call(0x204),                      // 0x200
+            Jump(0x200),                      // 0x202
+            SetImmediate { vx: 0, kk: 0xFF }, // 0x204
+            SetIndex(0xF80),                  // 0x206
+            AddIndex(0),                      // 0x208
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Strict));
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        let error = machine.step().unwrap_err();
+        let Error::Fault(fault) = &error else {
+            panic!("expected a fault, got {:?}", error);
+        };
+
+        assert!(matches!(error.cause(), Error::IndexOverflow(0x107F)));
+        assert_eq!(fault.pc, 0x208);
+        assert_eq!(fault.opcode, Some(0xF01E));
+        assert_eq!(fault.instruction, Some(AddIndex(0)));
+        assert_eq!(fault.cycles, 3);
+        assert_eq!(fault.call_stack, vec![0x202]);
+        assert_eq!(
+            error.to_string(),
+            "fault at 0x208 (F01E AddIndex(0)) after 3 cycles, call stack: 0x202"
+        );
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "index 0x107F is beyond memory"
+        );
+    }
 }
@@ -0,0 +1,302 @@
+// Hexadecimal fonts loaded into memory for FX29. interpreters shipped
+// different glyphs, the registry holds the known sets by name and custom
+// fonts can be built from raw bytes.
+
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// 16 glyphs of 5 rows
+pub const SMALL_SIZE: usize = 80;
+pub const SMALL_HEIGHT: u16 = 5;
+// glyphs of 10 rows, SCHIP had digits only, Octo all 16
+pub const BIG_HEIGHT: u16 = 10;
+
+pub const COSMAC_VIP: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+pub const ETI_660: [u8; SMALL_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+pub const DREAM_6800: [u8; SMALL_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+pub const FISH_N_CHIPS: [u8; SMALL_SIZE] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+pub const OCTO: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+pub const SUPERCHIP_BIG: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+pub const OCTO_BIG: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// a small font and an optional big one, the big font is stored
+// right after the small one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    small: Vec<u8>,
+    big: Vec<u8>,
+}
+
+impl Font {
+    // small is 80 bytes, big is made of 10 byte glyphs and may be empty
+    pub fn from_bytes(small: &[u8], big: &[u8]) -> Result<Self> {
+        if small.len() != SMALL_SIZE {
+            return Err(Error::InvalidFont(format!(
+                "small font is {} bytes, expected {}",
+                small.len(),
+                SMALL_SIZE
+            )));
+        }
+
+        if !big.len().is_multiple_of(BIG_HEIGHT as usize) || big.len() > 16 * BIG_HEIGHT as usize {
+            return Err(Error::InvalidFont(format!(
+                "big font is {} bytes, expected up to 16 glyphs of {}",
+                big.len(),
+                BIG_HEIGHT
+            )));
+        }
+
+        Ok(Self {
+            small: small.to_vec(),
+            big: big.to_vec(),
+        })
+    }
+
+    pub fn small(&self) -> &[u8] {
+        &self.small
+    }
+
+    pub fn big(&self) -> &[u8] {
+        &self.big
+    }
+
+    // address of a small glyph when the font is loaded at base
+    pub fn small_glyph(&self, base: u16, digit: u8) -> u16 {
+        base + (digit & 0xF) as u16 * SMALL_HEIGHT
+    }
+
+    // address of a big glyph when the font is loaded at base
+    pub fn big_glyph(&self, base: u16, digit: u8) -> u16 {
+        base + SMALL_SIZE as u16 + (digit & 0xF) as u16 * BIG_HEIGHT
+    }
+
+    // small and big font as they are laid out in memory
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.small.iter().chain(self.big.iter()).copied()
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self {
+            small: OCTO.to_vec(),
+            big: OCTO_BIG.to_vec(),
+        }
+    }
+}
+
+// fonts by name, filled with the built-in sets
+#[derive(Debug, Clone)]
+pub struct FontRegistry {
+    fonts: BTreeMap<String, Font>,
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            fonts: BTreeMap::new(),
+        };
+
+        let builtin: [(&str, &[u8], &[u8]); 6] = [
+            ("cosmac-vip", &COSMAC_VIP, &[]),
+            ("eti-660", &ETI_660, &[]),
+            ("dream-6800", &DREAM_6800, &[]),
+            ("fish-n-chips", &FISH_N_CHIPS, &[]),
+            ("superchip", &OCTO, &SUPERCHIP_BIG),
+            ("octo", &OCTO, &OCTO_BIG),
+        ];
+        for (name, small, big) in builtin {
+            registry.register(name, Font::from_bytes(small, big).unwrap());
+        }
+
+        registry
+    }
+
+    // adds a font, replacing one with the same name
+    pub fn register(&mut self, name: &str, font: Font) {
+        self.fonts.insert(name.to_string(), font);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Font> {
+        self.fonts.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fonts.keys().map(String::as_str)
+    }
+}
+
+impl Default for FontRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{COSMAC_VIP, Font, FontRegistry};
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = FontRegistry::new();
+        assert_eq!(registry.names().count(), 6);
+        assert_eq!(registry.get("cosmac-vip").unwrap().small(), COSMAC_VIP);
+        assert_eq!(registry.get("superchip").unwrap().big().len(), 100);
+
+        assert!(Font::from_bytes(&[0; 79], &[]).is_err());
+        assert!(Font::from_bytes(&[0; 80], &[0; 15]).is_err());
+
+        let custom = Font::from_bytes(&[0xAA; 80], &[]).unwrap();
+        registry.register("custom", custom.clone());
+        assert_eq!(registry.get("custom"), Some(&custom));
+    }
+
+    #[test]
+    fn test_load_font() {
+        let mut config = Config::for_profile(Profile::Modern);
+        config.font = FontRegistry::new().get("cosmac-vip").unwrap().clone();
+        config.memory_map.font_base = 0x100;
+
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 0x1B }, // 0x200
+            LoadFont(0),                      // 0x202
+            LoadRegisters(4),                 // 0x204
+        ]);
+
+        let mut machine = Machine::with_config(config);
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        // only the low nibble selects the glyph, like on the VIP
+        assert_eq!(machine.get_index(), 0x100 + 0xB * 5);
+        assert_eq!(machine.get_registers()[..5], COSMAC_VIP[55..60]);
+    }
+}
@@ -1,7 +1,3 @@
-use super::error::Error;
-
-type Result<T> = std::result::Result<T, Error>;
-
 mod decoder;
 mod encoder;
 
@@ -52,4 +48,122 @@ pub enum Instruction {
     StoreBcd(u8), // FX33: mem[I] = Vx / 100, mem[I+1] = (Vx / 10) % 10, mem[I+2] = Vx % 10
     StoreRegisters(u8), // FX55: mem[I] = v0, mem[I+1] = v1, ..., mem[I+n] = Vx
     LoadRegisters(u8), // FX65: v0 = mem[I], v1 = mem[I+1], ..., Vx = mem[I+n]
+
+    // CHIP-8X
+    CycleBackground,                        // 02A0: next background color
+    AddNibbles { vx: u8, vy: u8 },          // 5XY1: add each nibble of Vy to Vx, no carry
+    SetColorZones { vx: u8, vy: u8 },       // BXY0: color Vy of 8x4 zones in Vx, Vx+1
+    SetColorRows { vx: u8, vy: u8, n: u8 }, // BXYN: color Vy of N rows at (Vx, Vx+1)
+    SkipIfKey2(u8),                         // EXF2: skip next if keyPressed2(Vx)
+    SkipIfNotKey2(u8),                      // EXF5: skip next if !keyPressed2(Vx)
+    OutputPort(u8),                         // FXF8: port = Vx
+    InputPort(u8),                          // FXFB: Vx = port
+
+    // MegaChip
+    MegaOff,            // 0010: back to the CHIP-8 display
+    MegaOn,             // 0011: 256x192 palette display
+    LongIndex(u8),      // 01NN NNNN: I = NN NNNN, the next word is the low part
+    LoadPalette(u8),    // 02NN: palette entries 1..=NN = ARGB colors at mem[I]
+    SpriteWidth(u8),    // 03NN: sprite width = NN, 0 for 256
+    SpriteHeight(u8),   // 04NN: sprite height = NN, 0 for 256
+    ScreenAlpha(u8),    // 05NN: screen brightness = NN
+    PlaySample(u8),     // 060N: play the sample at mem[I], looping if N is 0
+    StopSample,         // 0700: stop the sample
+    BlendMode(u8),      // 080N: sprite blend mode = N
+    CollisionColor(u8), // 09NN: palette index colliding sprites hit
+
+    // CHIP-8E
+    Stop,                             // 00ED: stop the program
+    SkipIfGreater { vx: u8, vy: u8 }, // 5XY1: skip next if Vx > Vy
+    StoreRange { vx: u8, vy: u8 },    // 5XY2: mem[I..] = Vx..=Vy, I += count
+    LoadRange { vx: u8, vy: u8 },     // 5XY3: Vx..=Vy = mem[I..], I += count
+    JumpBack(u8),                     // BBNN: pc -= NN
+    JumpForward(u8),                  // BFNN: pc += NN
+    OutputPort3(u8),                  // FX03: port 3 = Vx
+    WaitInputPort3(u8),               // FXE3: wait for the strobe, Vx = port 3
+    InputPort3(u8),                   // FXE7: Vx = port 3
+}
+
+// instruction set variants, opcodes they reuse decode differently
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Dialect {
+    #[default]
+    Chip8,
+    Chip8X,
+    MegaChip,
+    HiRes,
+    Chip8E,
+}
+
+impl Instruction {
+    // variant name without operands, used to group instructions by kind
+    pub fn name(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            Clear => "Clear",
+            Return => "Return",
+            Syscall(_) => "Syscall",
+            Jump(_) => "Jump",
+            Call(_) => "Call",
+            SkipIfEqualImm { .. } => "SkipIfEqualImm",
+            SkipIfNotEqualImm { .. } => "SkipIfNotEqualImm",
+            SkipIfEqual { .. } => "SkipIfEqual",
+            SetImmediate { .. } => "SetImmediate",
+            AddImmediate { .. } => "AddImmediate",
+            Set { .. } => "Set",
+            Or { .. } => "Or",
+            And { .. } => "And",
+            Xor { .. } => "Xor",
+            Add { .. } => "Add",
+            Subtract { .. } => "Subtract",
+            ShiftRight { .. } => "ShiftRight",
+            SubtractNegate { .. } => "SubtractNegate",
+            ShiftLeft { .. } => "ShiftLeft",
+            SkipIfNotEqual { .. } => "SkipIfNotEqual",
+            SetIndex(_) => "SetIndex",
+            JumpOffset(_) => "JumpOffset",
+            Rnd { .. } => "Rnd",
+            Draw { .. } => "Draw",
+            SkipIfKey(_) => "SkipIfKey",
+            SkipIfNotKey(_) => "SkipIfNotKey",
+            LoadDelayTimer(_) => "LoadDelayTimer",
+            WaitForKey(_) => "WaitForKey",
+            SetDelayTimer(_) => "SetDelayTimer",
+            SetSoundTimer(_) => "SetSoundTimer",
+            AddIndex(_) => "AddIndex",
+            LoadFont(_) => "LoadFont",
+            StoreBcd(_) => "StoreBcd",
+            StoreRegisters(_) => "StoreRegisters",
+            LoadRegisters(_) => "LoadRegisters",
+            CycleBackground => "CycleBackground",
+            AddNibbles { .. } => "AddNibbles",
+            SetColorZones { .. } => "SetColorZones",
+            SetColorRows { .. } => "SetColorRows",
+            SkipIfKey2(_) => "SkipIfKey2",
+            SkipIfNotKey2(_) => "SkipIfNotKey2",
+            OutputPort(_) => "OutputPort",
+            InputPort(_) => "InputPort",
+            MegaOff => "MegaOff",
+            MegaOn => "MegaOn",
+            LongIndex(_) => "LongIndex",
+            LoadPalette(_) => "LoadPalette",
+            SpriteWidth(_) => "SpriteWidth",
+            SpriteHeight(_) => "SpriteHeight",
+            ScreenAlpha(_) => "ScreenAlpha",
+            PlaySample(_) => "PlaySample",
+            StopSample => "StopSample",
+            BlendMode(_) => "BlendMode",
+            CollisionColor(_) => "CollisionColor",
+            Stop => "Stop",
+            SkipIfGreater { .. } => "SkipIfGreater",
+            StoreRange { .. } => "StoreRange",
+            LoadRange { .. } => "LoadRange",
+            JumpBack(_) => "JumpBack",
+            JumpForward(_) => "JumpForward",
+            OutputPort3(_) => "OutputPort3",
+            WaitInputPort3(_) => "WaitInputPort3",
+            InputPort3(_) => "InputPort3",
+        }
+    }
 }
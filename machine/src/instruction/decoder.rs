@@ -1,62 +1,159 @@
-use super::Instruction;
+use super::{Dialect, Instruction};
 use crate::error::Error;
 
 impl Instruction {
     pub fn decode(word: u16) -> Result<Self, Error> {
-        let nibbles = nibbles(word);
-        let (_, vx, vy, n) = nibbles;
-        let kk = (word & 0x00FF) as u8;
-        let nnn = word & 0x0FFF;
-
-        use Instruction::*;
+        Self::decode_for(word, Dialect::Chip8)
+    }
 
-        let inst = match nibbles {
-            (0x0, 0x0, 0xE, 0x0) => Clear,
-            (0x0, 0x0, 0xE, 0xE) => Return,
-            (0x0, _, _, _) => Syscall(nnn),
-
-            (0x1, _, _, _) => Jump(nnn),
-            (0x2, _, _, _) => Call(nnn),
-            (0x3, _, _, _) => SkipIfEqualImm { vx, kk },
-            (0x4, _, _, _) => SkipIfNotEqualImm { vx, kk },
-            (0xA, _, _, _) => SetIndex(nnn),
-            (0xB, _, _, _) => JumpOffset(nnn),
-            (0xC, _, _, _) => Rnd { vx, kk },
-            (0xD, _, _, _) => Draw { vx, vy, n },
-            (0x6, _, _, _) => SetImmediate { vx, kk },
-            (0x7, _, _, _) => AddImmediate { vx, kk },
-
-            (0x5, _, _, 0x0) => SkipIfEqual { vx, vy },
-            (0x8, _, _, 0x0) => Set { vx, vy },
-            (0x8, _, _, 0x1) => Or { vx, vy },
-            (0x8, _, _, 0x2) => And { vx, vy },
-            (0x8, _, _, 0x3) => Xor { vx, vy },
-            (0x8, _, _, 0x4) => Add { vx, vy },
-            (0x8, _, _, 0x5) => Subtract { vx, vy },
-            (0x8, _, _, 0x6) => ShiftRight { vx, vy },
-            (0x8, _, _, 0x7) => SubtractNegate { vx, vy },
-            (0x8, _, _, 0xE) => ShiftLeft { vx, vy },
-            (0x9, _, _, 0x0) => SkipIfNotEqual { vx, vy },
-
-            (0xE, _, 0x9, 0xE) => SkipIfKey(vx),
-            (0xE, _, 0xA, 0x1) => SkipIfNotKey(vx),
-            (0xF, _, 0x0, 0x7) => LoadDelayTimer(vx),
-            (0xF, _, 0x0, 0xA) => WaitForKey(vx),
-            (0xF, _, 0x1, 0x5) => SetDelayTimer(vx),
-            (0xF, _, 0x1, 0x8) => SetSoundTimer(vx),
-            (0xF, _, 0x1, 0xE) => AddIndex(vx),
-            (0xF, _, 0x2, 0x9) => LoadFont(vx),
-            (0xF, _, 0x3, 0x3) => StoreBcd(vx),
-            (0xF, _, 0x5, 0x5) => StoreRegisters(vx),
-            (0xF, _, 0x6, 0x5) => LoadRegisters(vx),
-
-            _ => return Err(Error::InvalidInstruction(word)),
+    // opcodes of the dialect take precedence over the CHIP-8 ones
+    pub fn decode_for(word: u16, dialect: Dialect) -> Result<Self, Error> {
+        let extension = match dialect {
+            Dialect::Chip8 => None,
+            Dialect::Chip8X => decode_chip8x(word),
+            Dialect::MegaChip => decode_megachip(word),
+            Dialect::HiRes => decode_hires(word),
+            Dialect::Chip8E => decode_chip8e(word),
         };
 
-        Ok(inst)
+        match extension {
+            Some(inst) => Ok(inst),
+            None => decode_chip8(word),
+        }
     }
 }
 
+fn decode_chip8x(word: u16) -> Option<Instruction> {
+    let nibbles = nibbles(word);
+    let (_, vx, vy, n) = nibbles;
+
+    use Instruction::*;
+
+    let inst = match nibbles {
+        (0x0, 0x2, 0xA, 0x0) => CycleBackground,
+        (0x5, _, _, 0x1) => AddNibbles { vx, vy },
+        (0xB, _, _, 0x0) => SetColorZones { vx, vy },
+        (0xB, _, _, _) => SetColorRows { vx, vy, n },
+        (0xE, _, 0xF, 0x2) => SkipIfKey2(vx),
+        (0xE, _, 0xF, 0x5) => SkipIfNotKey2(vx),
+        (0xF, _, 0xF, 0x8) => OutputPort(vx),
+        (0xF, _, 0xF, 0xB) => InputPort(vx),
+        _ => return None,
+    };
+
+    Some(inst)
+}
+
+fn decode_megachip(word: u16) -> Option<Instruction> {
+    let nibbles = nibbles(word);
+    let (_, _, _, n) = nibbles;
+    let kk = (word & 0x00FF) as u8;
+
+    use Instruction::*;
+
+    let inst = match nibbles {
+        (0x0, 0x0, 0x1, 0x0) => MegaOff,
+        (0x0, 0x0, 0x1, 0x1) => MegaOn,
+        (0x0, 0x1, _, _) => LongIndex(kk),
+        (0x0, 0x2, _, _) => LoadPalette(kk),
+        (0x0, 0x3, _, _) => SpriteWidth(kk),
+        (0x0, 0x4, _, _) => SpriteHeight(kk),
+        (0x0, 0x5, _, _) => ScreenAlpha(kk),
+        (0x0, 0x6, 0x0, _) => PlaySample(n),
+        (0x0, 0x7, 0x0, 0x0) => StopSample,
+        (0x0, 0x8, 0x0, _) => BlendMode(n),
+        (0x0, 0x9, _, _) => CollisionColor(kk),
+        _ => return None,
+    };
+
+    Some(inst)
+}
+
+fn decode_chip8e(word: u16) -> Option<Instruction> {
+    let nibbles = nibbles(word);
+    let (_, vx, vy, _) = nibbles;
+    let kk = (word & 0x00FF) as u8;
+
+    use Instruction::*;
+
+    let inst = match nibbles {
+        (0x0, 0x0, 0xE, 0xD) => Stop,
+        (0x5, _, _, 0x1) => SkipIfGreater { vx, vy },
+        (0x5, _, _, 0x2) => StoreRange { vx, vy },
+        (0x5, _, _, 0x3) => LoadRange { vx, vy },
+        (0xB, 0xB, _, _) => JumpBack(kk),
+        (0xB, 0xF, _, _) => JumpForward(kk),
+        (0xF, _, 0x0, 0x3) => OutputPort3(vx),
+        (0xF, _, 0xE, 0x3) => WaitInputPort3(vx),
+        (0xF, _, 0xE, 0x7) => InputPort3(vx),
+        _ => return None,
+    };
+
+    Some(inst)
+}
+
+// the 64x64 interpreter clears its larger display with 0230
+fn decode_hires(word: u16) -> Option<Instruction> {
+    match word {
+        0x0230 => Some(Instruction::Clear),
+        _ => None,
+    }
+}
+
+fn decode_chip8(word: u16) -> Result<Instruction, Error> {
+    let nibbles = nibbles(word);
+    let (_, vx, vy, n) = nibbles;
+    let kk = (word & 0x00FF) as u8;
+    let nnn = word & 0x0FFF;
+
+    use Instruction::*;
+
+    let inst = match nibbles {
+        (0x0, 0x0, 0xE, 0x0) => Clear,
+        (0x0, 0x0, 0xE, 0xE) => Return,
+        (0x0, _, _, _) => Syscall(nnn),
+
+        (0x1, _, _, _) => Jump(nnn),
+        (0x2, _, _, _) => Call(nnn),
+        (0x3, _, _, _) => SkipIfEqualImm { vx, kk },
+        (0x4, _, _, _) => SkipIfNotEqualImm { vx, kk },
+        (0xA, _, _, _) => SetIndex(nnn),
+        (0xB, _, _, _) => JumpOffset(nnn),
+        (0xC, _, _, _) => Rnd { vx, kk },
+        (0xD, _, _, _) => Draw { vx, vy, n },
+        (0x6, _, _, _) => SetImmediate { vx, kk },
+        (0x7, _, _, _) => AddImmediate { vx, kk },
+
+        (0x5, _, _, 0x0) => SkipIfEqual { vx, vy },
+        (0x8, _, _, 0x0) => Set { vx, vy },
+        (0x8, _, _, 0x1) => Or { vx, vy },
+        (0x8, _, _, 0x2) => And { vx, vy },
+        (0x8, _, _, 0x3) => Xor { vx, vy },
+        (0x8, _, _, 0x4) => Add { vx, vy },
+        (0x8, _, _, 0x5) => Subtract { vx, vy },
+        (0x8, _, _, 0x6) => ShiftRight { vx, vy },
+        (0x8, _, _, 0x7) => SubtractNegate { vx, vy },
+        (0x8, _, _, 0xE) => ShiftLeft { vx, vy },
+        (0x9, _, _, 0x0) => SkipIfNotEqual { vx, vy },
+
+        (0xE, _, 0x9, 0xE) => SkipIfKey(vx),
+        (0xE, _, 0xA, 0x1) => SkipIfNotKey(vx),
+        (0xF, _, 0x0, 0x7) => LoadDelayTimer(vx),
+        (0xF, _, 0x0, 0xA) => WaitForKey(vx),
+        (0xF, _, 0x1, 0x5) => SetDelayTimer(vx),
+        (0xF, _, 0x1, 0x8) => SetSoundTimer(vx),
+        (0xF, _, 0x1, 0xE) => AddIndex(vx),
+        (0xF, _, 0x2, 0x9) => LoadFont(vx),
+        (0xF, _, 0x3, 0x3) => StoreBcd(vx),
+        (0xF, _, 0x5, 0x5) => StoreRegisters(vx),
+        (0xF, _, 0x6, 0x5) => LoadRegisters(vx),
+
+        _ => return Err(Error::InvalidInstruction(word)),
+    };
+
+    Ok(inst)
+}
+
 fn nibbles(word: u16) -> (u8, u8, u8, u8) {
     (
         ((word & 0xF000) >> 12) as u8,
@@ -76,7 +173,7 @@ mod nibbles {
     fn test_nibbles() {
         use super::nibbles;
 
-        let word = 0xE79E as u16; // EX9E
+        let word: u16 = 0xE79E; // EX9E
         let n = nibbles(word);
         assert_eq!(0xE, n.0);
         assert_eq!(0x7, n.1);
@@ -138,4 +235,92 @@ mod nibbles {
             assert_eq!(*want, got, "failed to decode opcode 0x{:04X}", *opcode)
         }
     }
+
+    #[test]
+    fn test_decode_chip8x() {
+        use crate::instruction::Dialect;
+        use Instruction::*;
+
+        let table = HashMap::from([
+            (0x02A0, CycleBackground),
+            (0x5AB1, AddNibbles { vx: 0xA, vy: 0xB }),
+            (0xBAB0, SetColorZones { vx: 0xA, vy: 0xB }),
+            (
+                0xBAB3,
+                SetColorRows {
+                    vx: 0xA,
+                    vy: 0xB,
+                    n: 0x3,
+                },
+            ),
+            (0xEAF2, SkipIfKey2(0xA)),
+            (0xEAF5, SkipIfNotKey2(0xA)),
+            (0xFAF8, OutputPort(0xA)),
+            (0xFAFB, InputPort(0xA)),
+            // the rest of the instruction set is unchanged
+            (0x02A2, Syscall(0x2A2)),
+            (0x5AB0, SkipIfEqual { vx: 0xA, vy: 0xB }),
+        ]);
+
+        for (opcode, want) in table.iter() {
+            let got = Instruction::decode_for(*opcode, Dialect::Chip8X).unwrap();
+            assert_eq!(*want, got, "failed to decode opcode 0x{:04X}", *opcode)
+        }
+
+        assert_eq!(Instruction::decode(0xBAB0).unwrap(), JumpOffset(0xAB0));
+        assert!(Instruction::decode(0xFAF8).is_err());
+    }
+
+    #[test]
+    fn test_decode_megachip() {
+        use crate::instruction::Dialect;
+        use Instruction::*;
+
+        let table = HashMap::from([
+            (0x0010, MegaOff),
+            (0x0011, MegaOn),
+            (0x0112, LongIndex(0x12)),
+            (0x02FF, LoadPalette(0xFF)),
+            (0x0310, SpriteWidth(0x10)),
+            (0x0400, SpriteHeight(0x00)),
+            (0x0580, ScreenAlpha(0x80)),
+            (0x0601, PlaySample(0x1)),
+            (0x0700, StopSample),
+            (0x0803, BlendMode(0x3)),
+            (0x09AB, CollisionColor(0xAB)),
+            (0x00E0, Clear),
+            (0x0710, Syscall(0x710)),
+        ]);
+
+        for (opcode, want) in table.iter() {
+            let got = Instruction::decode_for(*opcode, Dialect::MegaChip).unwrap();
+            assert_eq!(*want, got, "failed to decode opcode 0x{:04X}", *opcode)
+        }
+    }
+
+    #[test]
+    fn test_decode_chip8e() {
+        use crate::instruction::Dialect;
+        use Instruction::*;
+
+        let table = HashMap::from([
+            (0x00ED, Stop),
+            (0x5AB1, SkipIfGreater { vx: 0xA, vy: 0xB }),
+            (0x5AB2, StoreRange { vx: 0xA, vy: 0xB }),
+            (0x5AB3, LoadRange { vx: 0xA, vy: 0xB }),
+            (0xBB10, JumpBack(0x10)),
+            (0xBF02, JumpForward(0x02)),
+            (0xFA03, OutputPort3(0xA)),
+            (0xFAE3, WaitInputPort3(0xA)),
+            (0xFAE7, InputPort3(0xA)),
+            // other BNNN still jump with V0
+            (0xB200, JumpOffset(0x200)),
+            (0x5AB0, SkipIfEqual { vx: 0xA, vy: 0xB }),
+        ]);
+
+        for (opcode, want) in table.iter() {
+            let got = Instruction::decode_for(*opcode, Dialect::Chip8E).unwrap();
+            assert_eq!(*want, got, "failed to decode opcode 0x{:04X}", *opcode)
+        }
+    }
 }
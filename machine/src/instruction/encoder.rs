@@ -45,11 +45,40 @@ impl Instruction {
             StoreBcd(vx) => QXKK(0xF, vx, 0x33),
             StoreRegisters(x) => QXKK(0xF, x, 0x55),
             LoadRegisters(x) => QXKK(0xF, x, 0x65),
+            CycleBackground => QQQQ(0x02A0),
+            AddNibbles { vx, vy } => QXYW(0x5, vx, vy, 0x1),
+            SetColorZones { vx, vy } => QXYW(0xB, vx, vy, 0x0),
+            SetColorRows { vx, vy, n } => QXYW(0xB, vx, vy, n),
+            SkipIfKey2(vx) => QXKK(0xE, vx, 0xF2),
+            SkipIfNotKey2(vx) => QXKK(0xE, vx, 0xF5),
+            OutputPort(vx) => QXKK(0xF, vx, 0xF8),
+            InputPort(vx) => QXKK(0xF, vx, 0xFB),
+            MegaOff => QQQQ(0x0010),
+            MegaOn => QQQQ(0x0011),
+            LongIndex(nn) => QXKK(0x0, 0x1, nn),
+            LoadPalette(nn) => QXKK(0x0, 0x2, nn),
+            SpriteWidth(nn) => QXKK(0x0, 0x3, nn),
+            SpriteHeight(nn) => QXKK(0x0, 0x4, nn),
+            ScreenAlpha(nn) => QXKK(0x0, 0x5, nn),
+            PlaySample(n) => QXYW(0x0, 0x6, 0x0, n),
+            StopSample => QQQQ(0x0700),
+            BlendMode(n) => QXYW(0x0, 0x8, 0x0, n),
+            CollisionColor(nn) => QXKK(0x0, 0x9, nn),
+            Stop => QQQQ(0x00ED),
+            SkipIfGreater { vx, vy } => QXYW(0x5, vx, vy, 0x1),
+            StoreRange { vx, vy } => QXYW(0x5, vx, vy, 0x2),
+            LoadRange { vx, vy } => QXYW(0x5, vx, vy, 0x3),
+            JumpBack(nn) => QXKK(0xB, 0xB, nn),
+            JumpForward(nn) => QXKK(0xB, 0xF, nn),
+            OutputPort3(vx) => QXKK(0xF, vx, 0x03),
+            WaitInputPort3(vx) => QXKK(0xF, vx, 0xE3),
+            InputPort3(vx) => QXKK(0xF, vx, 0xE7),
         }
         .into()
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 enum Opcode {
     QQQQ(u16),            // covers 00E0: 2 bytes const
     QNNN(u8, u16),        // covers 1NNN: const, dyn NNN
@@ -87,13 +116,11 @@ impl From<Instruction> for u16 {
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
-
     #[test]
     fn test_encode() {
         use super::Instruction::*;
 
-        let table = HashMap::from([
+        let table = vec![
             (0x00E0, Clear),
             (0x00EE, Return),
             (0x0ABC, Syscall(0xABC)),
@@ -136,7 +163,42 @@ mod test {
             (0xFA33, StoreBcd(0xA)),
             (0xFA55, StoreRegisters(0xA)),
             (0xFA65, LoadRegisters(0xA)),
-        ]);
+            (0x02A0, CycleBackground),
+            (0x5AB1, AddNibbles { vx: 0xA, vy: 0xB }),
+            (0xBAB0, SetColorZones { vx: 0xA, vy: 0xB }),
+            (
+                0xBAB3,
+                SetColorRows {
+                    vx: 0xA,
+                    vy: 0xB,
+                    n: 0x3,
+                },
+            ),
+            (0xEAF2, SkipIfKey2(0xA)),
+            (0xEAF5, SkipIfNotKey2(0xA)),
+            (0xFAF8, OutputPort(0xA)),
+            (0xFAFB, InputPort(0xA)),
+            (0x0010, MegaOff),
+            (0x0011, MegaOn),
+            (0x0112, LongIndex(0x12)),
+            (0x0201, LoadPalette(0x01)),
+            (0x0310, SpriteWidth(0x10)),
+            (0x0420, SpriteHeight(0x20)),
+            (0x05FF, ScreenAlpha(0xFF)),
+            (0x0600, PlaySample(0x0)),
+            (0x0700, StopSample),
+            (0x0805, BlendMode(0x5)),
+            (0x0901, CollisionColor(0x01)),
+            (0x00ED, Stop),
+            (0x5AB1, SkipIfGreater { vx: 0xA, vy: 0xB }),
+            (0x5AB2, StoreRange { vx: 0xA, vy: 0xB }),
+            (0x5AB3, LoadRange { vx: 0xA, vy: 0xB }),
+            (0xBB04, JumpBack(0x04)),
+            (0xBF06, JumpForward(0x06)),
+            (0xFA03, OutputPort3(0xA)),
+            (0xFAE3, WaitInputPort3(0xA)),
+            (0xFAE7, InputPort3(0xA)),
+        ];
 
         for (want, inst) in table.iter() {
             let got = inst.encode();
@@ -1,5 +1,4 @@
-use crate::error::Error;
-
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Keyboard(u16);
 
 impl Keyboard {
@@ -41,11 +40,63 @@ impl Keyboard {
     }
 
     pub fn get_first_pressed_key(&self) -> Option<u8> {
-        for i in 0..16 {
-            if self.is_key_pressed(i) {
-                return Some(i);
-            }
+        (0..16).find(|&i| self.is_key_pressed(i))
+    }
+}
+
+// the CHIP-8 keys of a 4x4 keypad, row by row from the top left.
+// frontends map the same grid of host keys onto it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Layout(pub [u8; 16]);
+
+impl Layout {
+    #[rustfmt::skip]
+    pub const COSMAC_VIP: Layout = Layout([
+        0x1, 0x2, 0x3, 0xC,
+        0x4, 0x5, 0x6, 0xD,
+        0x7, 0x8, 0x9, 0xE,
+        0xA, 0x0, 0xB, 0xF,
+    ]);
+
+    #[rustfmt::skip]
+    pub const ETI_660: Layout = Layout([
+        0x1, 0x2, 0x3, 0xC,
+        0x4, 0x5, 0x6, 0xD,
+        0x7, 0x8, 0x9, 0xE,
+        0x0, 0xA, 0xB, 0xF,
+    ]);
+
+    #[rustfmt::skip]
+    pub const DREAM_6800: Layout = Layout([
+        0xC, 0xD, 0xE, 0xF,
+        0x8, 0x9, 0xA, 0xB,
+        0x4, 0x5, 0x6, 0x7,
+        0x0, 0x1, 0x2, 0x3,
+    ]);
+
+    // CHIP-8 key at the given position of the grid
+    pub fn key(&self, row: usize, column: usize) -> Option<u8> {
+        if row > 3 || column > 3 {
+            return None;
         }
-        None
+        Some(self.0[row * 4 + column])
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::COSMAC_VIP
+    }
+}
+
+impl From<u16> for Keyboard {
+    fn from(keys: u16) -> Self {
+        Self(keys)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,11 +1,26 @@
-mod display;
-mod error;
-mod instruction;
-mod keyboard;
-mod machine;
-mod memory;
-mod platform;
-mod program;
+pub mod batch;
+pub mod cheat;
+pub mod checksum;
+pub mod coverage;
+pub mod display;
+pub mod env;
+pub mod error;
+pub mod font;
+pub mod instruction;
+pub mod keyboard;
+pub mod machine;
+pub mod megachip;
+pub mod memory;
+pub mod movie;
+pub mod patch;
+pub mod phosphor;
+pub mod platform;
+pub mod profiler;
+pub mod program;
+pub mod recompiler;
+pub mod render;
+pub mod search;
+pub mod vip;
 
 #[cfg(test)]
 mod tests {
@@ -1,46 +1,99 @@
 use std::time::Duration;
 
+use crate::cheat::CheatList;
+use crate::coverage::Coverage;
 use crate::display::Display;
-use crate::instruction::Instruction;
+use crate::error::{Error, Fault};
+use crate::instruction::{Dialect, Instruction};
 use crate::keyboard::Keyboard;
+use crate::megachip::MegaChip;
+use crate::memory::Memory;
 use crate::platform::{ExecutionMode, Platform};
-use crate::{error::Error, memory::Memory};
+use crate::profiler::Profiler;
+use crate::recompiler;
+use crate::vip::Vip;
+use faults::Policy;
+use rand::SeedableRng;
 use rand::rngs::SmallRng;
-use rand::{Rng, RngCore, SeedableRng};
 
 type Result<T> = std::result::Result<T, Error>;
 
 pub mod config;
+pub mod faults;
+pub mod hooks;
+pub mod native;
 pub mod quircks;
+pub mod snapshot;
 
 mod ops_alu;
 mod ops_control;
 mod ops_io;
+mod ops_megachip;
 mod ops_memory;
 mod ops_register;
 mod ops_system;
 
 mod debug;
+mod machine_code;
+
+#[derive(Debug, Clone)]
+pub enum Status {
+    Running,
+    // stopped by a fault with the Halt policy, holds the Error::Fault
+    Halted(Error),
+    // stopped by the program with 00ED
+    Stopped,
+}
 
+#[derive(Clone)]
 pub struct Machine {
     memory: Memory,
     display: Display,
     config: config::Config,
 
     registers: [u8; 16], // V0 to FF registers
-    stack: [u16; 16],
+    stack: Vec<u16>,
     pc: u16,    // program counter register
     sp: u8,     // stack counter register
     dt: u8,     // delay timer register
     st: u8,     // sound timer register
-    index: u16, // index register (I)
+    index: u32, // index register (I), 24 bits wide on MegaChip
+    status: Status,
 
     keys: Keyboard,
+    // CHIP-8X second keypad
+    keys2: Keyboard,
+    // CHIP-8X IO port, the output is passed to the platform once per frame
+    input_port: u8,
+    output_port: u8,
+    output_written: bool,
+    // MegaChip screen and sound, created by the first MegaChip instruction
+    megachip: Option<Box<MegaChip>>,
+    sample_changed: bool,
+    // set when a new value arrived on the input port, FXE3 waits for it
+    input_strobe: bool,
+    // runs the program with the original interpreter instead of exec
+    vip: Option<Box<Vip>>,
     rng: SmallRng,
 
+    // ahead-of-time compiled version of the loaded program, see recompiler
+    native: Option<native::NativeProgram>,
+    cheats: CheatList,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+
+    // memory access observers, see hooks
+    hooks: Vec<(hooks::HookId, hooks::Hook)>,
+    next_hook: u64,
+
     last_frame_time: Duration,
     timer_period: Duration,
     timer_accumulator: Duration,
+
+    // CPU cycles carried over between frames by step_frame
+    frame_cycles: u32,
+    // instructions executed since the last reset
+    cycles: u64,
 }
 
 impl Machine {
@@ -51,19 +104,36 @@ impl Machine {
             display: Display::new(),
             config: config::Config::default(),
             keys: Keyboard::new(),
+            keys2: Keyboard::new(),
+            input_port: 0,
+            output_port: 0,
+            output_written: false,
+            megachip: None,
+            sample_changed: false,
+            input_strobe: false,
+            vip: None,
 
             registers: [0; 16],
-            stack: [0; 16],
+            stack: vec![0; cfg.memory_map.stack_depth as usize],
             pc: 0,
             sp: 0,
             dt: 0,
             st: 0,
             index: 0,
+            status: Status::Running,
 
             rng: SmallRng::from_rng(&mut rand::rng()),
+            native: None,
+            cheats: CheatList::new(),
+            profiler: None,
+            coverage: None,
+            hooks: Vec::new(),
+            next_hook: 0,
             last_frame_time: Duration::new(0, 0),
             timer_period: Duration::from_millis(1000 / cfg.timer_frequency as u64),
             timer_accumulator: Duration::new(0, 0),
+            frame_cycles: 0,
+            cycles: 0,
         }
     }
 
@@ -75,28 +145,56 @@ impl Machine {
         machine
     }
 
+    // reseed replaces the random generator with a seeded one
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
     pub fn with_config(cfg: config::Config) -> Self {
         let mut machine = Self::new();
         machine.timer_period = Duration::from_millis(1000 / cfg.timer_frequency as u64);
         machine.config = cfg;
+        machine.reset();
         machine
     }
 
     // reset display buffer, memory, keyboard input, registers, stack, timers,
     // index register. it does not reset random generator.
     pub fn reset(&mut self) {
-        self.memory = Memory::new();
+        let map = &self.config.memory_map;
+        self.memory = Memory::with_map(map, &self.config.font);
+        if let Some(vip) = &mut self.vip {
+            vip.reset(&mut self.memory, &self.config.font);
+        }
         self.keys.clear_all_keys();
+        self.keys2.clear_all_keys();
+        self.input_port = 0;
+        self.input_strobe = false;
+        self.output_port = 0;
+        self.output_written = false;
+        self.megachip = None;
+        self.sample_changed = false;
+        if self.display.geometry() != self.config.geometry {
+            self.display = Display::with_geometry(self.config.geometry);
+        }
         self.display.clear();
+        self.display.reset_colors();
         self.registers = [0; 16];
-        self.stack = [0; 16];
+        self.stack = vec![0; map.stack_depth as usize];
         self.dt = 0;
         self.st = 0;
         self.pc = 0;
         self.sp = 0;
         self.index = 0;
+        self.status = Status::Running;
         self.timer_accumulator = Duration::new(0, 0);
         self.last_frame_time = Duration::new(0, 0);
+        self.frame_cycles = 0;
+        self.cycles = 0;
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.unwind();
+        }
     }
 
     pub fn run_frame<P: Platform>(
@@ -105,64 +203,387 @@ impl Machine {
     ) -> std::result::Result<bool, P::Error> {
         let mode = platform.get_execution_mode();
         let frame_start = platform.get_time();
+        let elapsed = frame_start.saturating_sub(self.last_frame_time);
 
-        self.keys = platform.get_keys();
+        self.set_keys(platform.get_keys());
+        self.set_second_keys(platform.get_second_keys());
+        let input_port = platform.get_input_port();
+        if input_port != self.input_port {
+            self.set_input_port(input_port);
+        }
+        self.cheats.apply(&mut self.memory)?;
 
-        let instructions_to_run = match mode {
-            ExecutionMode::Paused => 0,
-            ExecutionMode::Step => 1,
-            ExecutionMode::Running => self.calculate_instructions_for_frame(frame_start),
-        };
+        if let Some(vip) = self.vip.as_deref_mut() {
+            if !matches!(self.status, Status::Running) {
+                return Ok(false);
+            }
 
-        for _ in 0..instructions_to_run {
-            if !self.step()? {
+            // the interpreter counts its timers on the video interrupt
+            match mode {
+                ExecutionMode::Paused => {}
+                ExecutionMode::Step => vip.step(&mut self.memory, &mut self.display, self.keys),
+                ExecutionMode::Running => {
+                    vip.run_frame(&mut self.memory, &mut self.display, self.keys)
+                }
+            }
+        } else {
+            let instructions_to_run = match mode {
+                ExecutionMode::Paused => 0,
+                ExecutionMode::Step => 1,
+                ExecutionMode::Running => self.calculate_instructions_for_frame(frame_start),
+            };
+
+            if !self.run_instructions(instructions_to_run)? {
                 return Ok(false);
             }
+
+            if matches!(mode, ExecutionMode::Running) {
+                self.update_timers(elapsed);
+            }
+        }
+
+        if let Some(megachip) = self.megachip.as_deref().filter(|mega| mega.is_enabled()) {
+            platform.draw_megachip(megachip)?;
+        } else if !platform.only_changed_frames() {
+            platform.draw_display(&self.display)?;
+        } else if self.display.is_dirty() {
+            platform.draw_changes(&self.display, self.display.dirty())?;
+            self.display.clear_dirty();
         }
+        platform.play_sound(self.vip.as_ref().map_or(self.st > 0, |vip| vip.tone()))?;
+        if self.sample_changed {
+            self.sample_changed = false;
+            let sample = self.megachip.as_deref().and_then(MegaChip::sample);
+            platform.play_sample(sample)?;
+        }
+        if self.output_written {
+            self.output_written = false;
+            platform.write_port(self.output_port)?;
+        }
+        platform.end_frame(self)?;
+
+        Ok(true)
+    }
 
-        if matches!(mode, ExecutionMode::Running) {
-            let delta = platform.get_time() - frame_start;
-            self.update_timers(delta);
+    // runs a frame worth of instructions and ticks timers once, regardless
+    // of the wall clock. headless runners use it for deterministic execution,
+    // keys have to be provided with set_keys.
+    pub fn step_frame(&mut self) -> Result<bool> {
+        if let Some(vip) = self.vip.as_deref_mut() {
+            if !matches!(self.status, Status::Running) {
+                return Ok(false);
+            }
+
+            self.cheats.apply(&mut self.memory)?;
+            vip.run_frame(&mut self.memory, &mut self.display, self.keys);
+            return Ok(true);
         }
 
-        platform.draw_display(&self.display)?;
-        platform.play_sound(self.st > 0)?;
+        let timer_frequency = self.config.timer_frequency as u32;
+        self.frame_cycles += self.config.cpu_frequency as u32;
+
+        let instructions = self.frame_cycles / timer_frequency;
+        self.frame_cycles %= timer_frequency;
+
+        self.cheats.apply(&mut self.memory)?;
 
+        if !self.run_instructions(instructions)? {
+            return Ok(false);
+        }
+
+        self.tick_timers();
         Ok(true)
     }
 
+    pub fn set_keys(&mut self, keys: Keyboard) {
+        self.keys = self.scan(keys);
+    }
+
+    pub fn set_second_keys(&mut self, keys: Keyboard) {
+        self.keys2 = self.scan(keys);
+    }
+
+    // the keys the interpreter is able to see
+    fn scan(&self, keys: Keyboard) -> Keyboard {
+        if !self.config.quircks.single_key {
+            return keys;
+        }
+
+        let mut scanned = Keyboard::new();
+        if let Some(key) = keys.get_first_pressed_key() {
+            scanned.set_key(key, true);
+        }
+        scanned
+    }
+
+    pub fn set_input_port(&mut self, value: u8) {
+        self.input_port = value;
+        self.input_strobe = true;
+    }
+
+    // executes a single instruction, returns false once the machine halted
+    // or the program stopped. faults are handled according to config.faults,
+    // errors are wrapped in Error::Fault with the state of the machine at the
+    // failing instruction.
     pub fn step(&mut self) -> Result<bool> {
-        if self.pc < 0x200 || self.pc >= 0x1000 - 2 {
-            return Err(Error::InvalidProgramCounter(self.pc));
+        if !matches!(self.status, Status::Running) {
+            return Ok(false);
+        }
+
+        // a single CPU instruction, CHIP-8 instructions take dozens
+        if let Some(vip) = self.vip.as_deref_mut() {
+            vip.step(&mut self.memory, &mut self.display, self.keys);
+            return Ok(true);
         }
 
-        if self.pc % 2 != 0 {
-            return Err(Error::UnalignedProgramCounter(self.pc));
+        let pc = self.pc;
+        match self.execute() {
+            Ok(()) => Ok(matches!(self.status, Status::Running)),
+            Err(err) => match self.config.faults.policy(&err) {
+                Policy::Error => Err(err),
+                Policy::Ignore | Policy::Wrap => {
+                    // faults before the fetch would repeat forever
+                    if self.pc == pc {
+                        self.pc = pc.wrapping_add(2);
+                    }
+                    self.cycles += 1;
+                    Ok(true)
+                }
+                Policy::Halt => {
+                    self.status = Status::Halted(err);
+                    Ok(false)
+                }
+            },
         }
+    }
 
-        let word = self.memory.read_word(self.pc)?;
-        self.pc += 2;
+    fn execute(&mut self) -> Result<()> {
+        let wrap = self.config.faults.program_counter == Policy::Wrap;
+        if wrap {
+            self.pc = (self.pc as usize % self.memory.size()) as u16;
+        }
 
-        let instruction = Instruction::decode(word)?;
-        self.exec(instruction)?;
+        let pc = self.pc;
+        let program_start = self.config.memory_map.program_start;
 
-        Ok(true)
+        if (pc < program_start || pc as usize + 2 > self.memory.size()) && !wrap {
+            return Err(self.fault(Error::InvalidProgramCounter(pc), pc, None, None));
+        }
+
+        if !pc.is_multiple_of(2) && !wrap {
+            return Err(self.fault(Error::UnalignedProgramCounter(pc), pc, None, None));
+        }
+
+        let index = self.index;
+        let word = self
+            .fetch(pc)
+            .map_err(|err| self.fault(err, pc, None, None))?;
+        self.pc = pc.wrapping_add(2);
+
+        let instruction = Instruction::decode_for(word, self.config.profile.dialect())
+            .map_err(|err| self.fault(err, pc, Some(word), None))?;
+        self.exec(instruction)
+            .map_err(|err| self.fault(err, pc, Some(word), Some(instruction)))?;
+        self.cycles += 1;
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, instruction);
+        }
+
+        if self.coverage.is_some() {
+            let sprite = match instruction {
+                Instruction::Draw { n, .. } => self.sprite_length(n),
+                _ => 0,
+            };
+            if let Some(coverage) = &mut self.coverage {
+                coverage.record(pc, instruction, index, sprite);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fault(
+        &self,
+        error: Error,
+        pc: u16,
+        opcode: Option<u16>,
+        instruction: Option<Instruction>,
+    ) -> Error {
+        Error::Fault(Box::new(Fault {
+            error,
+            pc,
+            opcode,
+            instruction,
+            cycles: self.cycles,
+            call_stack: self.stack[..self.sp as usize].to_vec(),
+        }))
     }
 
     // resets CPU state and load program into memory
     pub fn load_program(&mut self, program: Vec<u16>) -> Result<()> {
+        self.config.memory_map.validate()?;
         self.reset();
 
-        let start_addr = 0x200;
+        let start_addr = self.config.memory_map.program_start;
         self.pc = start_addr;
 
         for (i, &word) in program.iter().enumerate() {
-            let addr = start_addr + (i * 2) as u16;
+            let addr = start_addr as u32 + (i * 2) as u32;
             self.memory.write_word(addr, word)?;
         }
+        self.patch_entry()
+    }
+
+    // resets CPU state and load raw ROM bytes into memory
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<()> {
+        self.config.memory_map.validate()?;
+        self.reset();
+
+        let start_addr = self.config.memory_map.program_start;
+        self.pc = start_addr;
+
+        for (i, &byte) in rom.iter().enumerate() {
+            self.memory.write(start_addr as u32 + i as u32, byte)?;
+        }
+        self.patch_entry()
+    }
+
+    // hires ROMs start with 1260, a jump into the part of the 64x64
+    // interpreter the VIP loaded at 0x200. it is not emulated, the jump
+    // goes straight to the program at 0x2C0 instead.
+    fn patch_entry(&mut self) -> Result<()> {
+        let start_addr = self.config.memory_map.program_start as u32;
+        if self.config.profile.dialect() == Dialect::HiRes
+            && self.memory.read_word(start_addr)? == 0x1260
+        {
+            self.memory.write_word(start_addr, 0x12C0)?;
+        }
+        Ok(())
+    }
+
+    // copies the display into memory when the framebuffer is mapped,
+    // called by the instructions changing the display
+    fn sync_framebuffer(&mut self) {
+        if let Some(base) = self.config.memory_map.framebuffer {
+            for (i, &byte) in self.display.framebuffer().iter().enumerate() {
+                // the map may put part of the framebuffer beyond memory
+                let _ = self.memory.write(base as u32 + i as u32, byte);
+            }
+        }
+    }
+
+    // attaches a natively compiled program produced by the recompiler.
+    // it must be built from the same ROM that is loaded into memory,
+    // code it can't handle is still executed by the interpreter. the
+    // recompiler only knows CHIP-8 loaded at recompiler::LOAD_ADDRESS,
+    // machines running other dialects or loading elsewhere refuse it.
+    pub fn set_native(&mut self, program: Option<native::NativeProgram>) -> Result<()> {
+        if program.is_some() {
+            let profile = self.config.profile;
+            if profile.dialect() != Dialect::Chip8 {
+                return Err(Error::InvalidNative(format!(
+                    "the {} profile does not run plain CHIP-8",
+                    profile.name()
+                )));
+            }
+
+            let start = self.config.memory_map.program_start;
+            if start != recompiler::LOAD_ADDRESS {
+                return Err(Error::InvalidNative(format!(
+                    "programs start at {:#05X} instead of {:#05X}",
+                    start,
+                    recompiler::LOAD_ADDRESS
+                )));
+            }
+        }
+
+        self.native = program;
+        Ok(())
+    }
+
+    // selects the COSMAC VIP backend, the program is then run by the given
+    // interpreter on an emulated VIP. it is copied below the program start
+    // and restarted on every reset, None returns to the built-in
+    // interpreter. the 1802 reads and writes memory directly, so hooks,
+    // the profiler, coverage, fault policies and the native program
+    // don't see what the VIP executes.
+    pub fn set_vip(&mut self, vip: Option<Vip>) -> Result<()> {
+        if let Some(vip) = &vip {
+            let size = vip.interpreter().len();
+            if size > self.config.memory_map.program_start as usize {
+                return Err(Error::InvalidInterpreter(size));
+            }
+        }
+
+        self.vip = vip.map(Box::new);
+        if let Some(vip) = &mut self.vip {
+            vip.reset(&mut self.memory, &self.config.font);
+        }
         Ok(())
     }
 
+    pub fn get_vip(&self) -> Option<&Vip> {
+        self.vip.as_deref()
+    }
+
+    // attaches a profiler counting every instruction executed by step,
+    // None detaches it. the native program is not used while it is attached.
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    // attaches a coverage map marking memory used by step, None detaches it.
+    // it is kept across resets to collect coverage over several runs, the
+    // native program is not used while it is attached.
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage;
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    // executes up to `count` instructions, returns false if execution stopped.
+    // straight-line code is handed to the native program if one is attached
+    // and nothing has to see every instruction, everything else goes through
+    // step one instruction at a time.
+    fn run_instructions(&mut self, count: u32) -> Result<bool> {
+        if !matches!(self.status, Status::Running) {
+            return Ok(false);
+        }
+
+        let mut remaining = count;
+
+        while remaining > 0 {
+            if let Some(program) = self.native.filter(|_| {
+                self.hooks.is_empty() && self.profiler.is_none() && self.coverage.is_none()
+            }) {
+                let mut ctx = native::Context::new(self, remaining);
+                let exit = program(&mut ctx);
+                let left = ctx.budget();
+                self.cycles += (remaining - left) as u64;
+                remaining = left;
+
+                // the interpreter would spin on FX0A for the rest of the frame
+                if remaining == 0 || matches!(exit, native::Exit::Wait) {
+                    break;
+                }
+            }
+
+            if !self.step()? {
+                return Ok(false);
+            }
+            remaining -= 1;
+        }
+
+        Ok(true)
+    }
+
     fn calculate_instructions_for_frame(&mut self, current_time: Duration) -> u32 {
         let delta = current_time - self.last_frame_time;
         let expected_instructions = self.config.cpu_frequency as f64 * delta.as_secs_f64();
@@ -174,10 +595,13 @@ impl Machine {
     fn update_timers(&mut self, delta: Duration) {
         self.timer_accumulator += delta;
 
-        if self.timer_accumulator < self.timer_period {
-            return;
+        while self.timer_accumulator >= self.timer_period {
+            self.tick_timers();
+            self.timer_accumulator -= self.timer_period;
         }
+    }
 
+    fn tick_timers(&mut self) {
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -185,8 +609,12 @@ impl Machine {
         if self.st > 0 {
             self.st -= 1;
         }
+    }
+}
 
-        self.timer_accumulator -= self.timer_period;
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -219,13 +647,51 @@ impl Machine {
         self.st
     }
 
-    pub fn get_index(&self) -> u16 {
+    pub fn get_index(&self) -> u32 {
         self.index
     }
+
+    pub fn get_megachip(&self) -> Option<&MegaChip> {
+        self.megachip.as_deref()
+    }
+
+    // last value written by FXF8
+    pub fn get_output_port(&self) -> u8 {
+        self.output_port
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn get_config(&self) -> &config::Config {
+        &self.config
+    }
+
+    // cheat codes applied at the start of every frame
+    pub fn cheats(&self) -> &CheatList {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut CheatList {
+        &mut self.cheats
+    }
 }
 
 impl Machine {
-    fn exec(&mut self, instruction: Instruction) -> Result<()> {
+    pub(super) fn exec(&mut self, instruction: Instruction) -> Result<()> {
         use Instruction::*;
 
         match instruction {
@@ -252,7 +718,7 @@ impl Machine {
             // register operations
             SetImmediate { vx, kk } => self.op_set_immediate(vx, kk),
             Set { vx, vy } => self.op_set(vx, vy),
-            SetIndex(addr) => self.op_set_index(addr),
+            SetIndex(addr) => self.op_set_index(addr.into()),
             AddIndex(x) => self.op_add_index(x),
 
             // ALU operaitions
@@ -277,6 +743,136 @@ impl Machine {
             StoreRegisters(x) => self.op_store_registers(x),
             LoadRegisters(x) => self.op_load_registers(x),
             LoadFont(vx) => self.op_load_font(vx),
+
+            // CHIP-8X operations
+            CycleBackground => self.op_cycle_background(),
+            AddNibbles { vx, vy } => self.op_add_nibbles(vx, vy),
+            SetColorZones { vx, vy } => self.op_set_color_zones(vx, vy),
+            SetColorRows { vx, vy, n } => self.op_set_color_rows(vx, vy, n),
+            SkipIfKey2(vx) => self.op_skip_if_key2(vx),
+            SkipIfNotKey2(vx) => self.op_skip_if_not_key2(vx),
+            OutputPort(vx) => self.op_output_port(vx),
+            InputPort(vx) => self.op_input_port(vx),
+
+            // MegaChip operations
+            MegaOff => self.op_mega_mode(false),
+            MegaOn => self.op_mega_mode(true),
+            LongIndex(nn) => self.op_long_index(nn),
+            LoadPalette(nn) => self.op_load_palette(nn),
+            SpriteWidth(nn) => self.op_sprite_width(nn),
+            SpriteHeight(nn) => self.op_sprite_height(nn),
+            ScreenAlpha(nn) => self.op_screen_alpha(nn),
+            PlaySample(n) => self.op_play_sample(n),
+            StopSample => self.op_stop_sample(),
+            BlendMode(n) => self.op_blend_mode(n),
+            CollisionColor(nn) => self.op_collision_color(nn),
+
+            // CHIP-8E operations
+            Stop => self.op_stop(),
+            SkipIfGreater { vx, vy } => self.op_skip_if_greater(vx, vy),
+            StoreRange { vx, vy } => self.op_store_range(vx, vy),
+            LoadRange { vx, vy } => self.op_load_range(vx, vy),
+            JumpBack(nn) => self.op_jump_relative(-(nn as i16)),
+            JumpForward(nn) => self.op_jump_relative(nn as i16),
+            OutputPort3(vx) => self.op_output_port(vx),
+            WaitInputPort3(vx) => self.op_wait_input_port(vx),
+            InputPort3(vx) => self.op_input_port(vx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::Machine;
+    use super::config::{Config, Profile};
+    use crate::display::Display;
+    use crate::error::Error;
+    use crate::instruction::Instruction::*;
+    use crate::keyboard::Keyboard;
+    use crate::platform::{ExecutionMode, Platform};
+    use crate::program::Program;
+
+    // frontend whose clock is set by the test
+    struct Clock {
+        now: Cell<Duration>,
+    }
+
+    impl Platform for Clock {
+        type Error = Error;
+
+        fn get_keys(&self) -> Keyboard {
+            Keyboard::new()
+        }
+
+        fn draw_display(&mut self, _: &Display) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn play_sound(&mut self, _: bool) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_time(&self) -> Duration {
+            self.now.get()
+        }
+
+        fn get_execution_mode(&self) -> ExecutionMode {
+            ExecutionMode::Running
         }
     }
+
+    #[test]
+    fn test_timers_follow_frame_time() {
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 60 },
+            SetDelayTimer(0),
+            Jump(0x204),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        let mut clock = Clock {
+            now: Cell::new(Duration::ZERO),
+        };
+
+        // timers tick once per 16 ms period of time elapsed since the
+        // previous frame, the remainder carries over to the next frame
+        for (ms, dt) in [(100, 54), (200, 48), (500, 29)] {
+            clock.now.set(Duration::from_millis(ms));
+            machine.run_frame(&mut clock).unwrap();
+            assert_eq!(machine.get_delay_timer(), dt);
+        }
+    }
+
+    #[test]
+    fn test_hires() {
+        let mut rom = vec![0; 0xCA + 1];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        rom[0xC0..].copy_from_slice(&[
+            0x60, 0x28, // V0 = 40
+            0xA2, 0xCA, // I = 0x2CA
+            0xD0, 0x01, // draw at (40, 40)
+            0x02, 0x30, // clear
+            0x12, 0xC8, // loop
+            0x80,
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::HiRes));
+        machine.load_rom(&rom).unwrap();
+        for _ in 0..4 {
+            machine.step().unwrap();
+        }
+
+        assert_eq!(machine.get_pc(), 0x2C6);
+        assert_eq!(machine.get_display().height(), 64);
+        assert!(machine.get_display().get_pixel(40, 40));
+        let memory = machine.get_memory();
+        assert_eq!(memory.read(0xE00 + 40 * 8 + 5).unwrap(), 0x80);
+
+        machine.step().unwrap();
+        assert!(!machine.get_display().get_pixel(40, 40));
+    }
 }
@@ -1,16 +1,204 @@
+use super::faults::{Faults, Policy};
 use super::quircks::Quircks;
+use crate::display::Geometry;
+use crate::font::{Font, FontRegistry};
+use crate::instruction::Dialect;
+use crate::keyboard::Layout;
+use crate::memory::MemoryMap;
+
+// platform whose behaviour the machine follows
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Profile {
+    // Octo and most current interpreters
+    #[default]
+    Modern,
+    // the original interpreter on the RCA COSMAC VIP
+    CosmacVip,
+    // the 64x64 VIP interpreter, ROMs start with the 1260 jump into it
+    HiRes,
+    // the VIP interpreter for the VP-590 color board and VP-595 sound
+    Chip8X,
+    // the VIP interpreter extended with range moves, relative jumps and
+    // port IO
+    Chip8E,
+    // the ETI-660 kit computer, 64x48 display
+    Eti660,
+    // the DREAM 6800, CHIPOS on a Motorola 6800
+    Dream6800,
+    // SCHIP 1.1 on the HP 48
+    SuperChip,
+    // SCHIP extended with a 256x192 palette display and samples
+    MegaChip,
+    // modern behaviour, but every fault is an error
+    Strict,
+}
+
+impl Profile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Modern => "modern",
+            Profile::CosmacVip => "cosmac-vip",
+            Profile::HiRes => "hires",
+            Profile::Chip8X => "chip-8x",
+            Profile::Chip8E => "chip-8e",
+            Profile::Eti660 => "eti-660",
+            Profile::Dream6800 => "dream-6800",
+            Profile::SuperChip => "superchip",
+            Profile::MegaChip => "megachip",
+            Profile::Strict => "strict",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "modern" => Some(Profile::Modern),
+            "cosmac-vip" => Some(Profile::CosmacVip),
+            "hires" => Some(Profile::HiRes),
+            "chip-8x" => Some(Profile::Chip8X),
+            "chip-8e" => Some(Profile::Chip8E),
+            "eti-660" => Some(Profile::Eti660),
+            "dream-6800" => Some(Profile::Dream6800),
+            "superchip" => Some(Profile::SuperChip),
+            "megachip" => Some(Profile::MegaChip),
+            "strict" => Some(Profile::Strict),
+            _ => None,
+        }
+    }
+
+    // how the platform reacts to faults, real interpreters never raised an
+    // error: addresses simply wrap and garbage opcodes do something random
+    pub fn faults(&self) -> Faults {
+        use Policy::*;
+
+        match self {
+            Profile::Modern => Faults {
+                index: Wrap,
+                stack: Halt,
+                instruction: Halt,
+                memory: Wrap,
+                program_counter: Wrap,
+            },
+            Profile::CosmacVip
+            | Profile::HiRes
+            | Profile::Chip8X
+            | Profile::Chip8E
+            | Profile::Eti660
+            | Profile::Dream6800 => Faults {
+                index: Wrap,
+                stack: Wrap,
+                instruction: Ignore,
+                memory: Wrap,
+                program_counter: Wrap,
+            },
+            Profile::SuperChip | Profile::MegaChip => Faults {
+                index: Wrap,
+                stack: Halt,
+                instruction: Ignore,
+                memory: Wrap,
+                program_counter: Wrap,
+            },
+            Profile::Strict => Faults::STRICT,
+        }
+    }
+
+    pub fn memory_map(&self) -> MemoryMap {
+        match self {
+            Profile::CosmacVip | Profile::Chip8E => MemoryMap::COSMAC_VIP,
+            Profile::HiRes => MemoryMap::HIRES,
+            Profile::Chip8X => MemoryMap::CHIP_8X,
+            Profile::Eti660 => MemoryMap::ETI_660,
+            Profile::MegaChip => MemoryMap::MEGACHIP,
+            _ => MemoryMap::default(),
+        }
+    }
+
+    pub fn font(&self) -> Font {
+        let name = match self {
+            Profile::CosmacVip | Profile::HiRes | Profile::Chip8X | Profile::Chip8E => "cosmac-vip",
+            Profile::Eti660 => "eti-660",
+            Profile::Dream6800 => "dream-6800",
+            Profile::SuperChip | Profile::MegaChip => "superchip",
+            _ => "octo",
+        };
+        FontRegistry::new().get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        match self {
+            Profile::HiRes => Geometry::HIRES,
+            Profile::Eti660 => Geometry::ETI_660,
+            _ => Geometry::CHIP_8,
+        }
+    }
+
+    pub fn keypad(&self) -> Layout {
+        match self {
+            Profile::Eti660 => Layout::ETI_660,
+            Profile::Dream6800 => Layout::DREAM_6800,
+            _ => Layout::COSMAC_VIP,
+        }
+    }
+
+    // both Australian machines count timers with the 50 Hz video
+    pub fn timer_frequency(&self) -> u16 {
+        match self {
+            Profile::Eti660 | Profile::Dream6800 => 50,
+            _ => 60,
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Profile::HiRes => Dialect::HiRes,
+            Profile::Chip8E => Dialect::Chip8E,
+            Profile::Chip8X => Dialect::Chip8X,
+            Profile::MegaChip => Dialect::MegaChip,
+            _ => Dialect::Chip8,
+        }
+    }
+
+    pub fn quircks(&self) -> Quircks {
+        Quircks {
+            shift: matches!(self, Profile::SuperChip | Profile::MegaChip),
+            single_key: matches!(self, Profile::Dream6800),
+            // opt-in, a stray 0NNN in a ROM would otherwise start running data
+            machine_code: false,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
+    pub profile: Profile,
     pub quircks: Quircks,
+    pub faults: Faults,
+    pub memory_map: MemoryMap,
+    pub geometry: Geometry,
+    pub keypad: Layout,
+    pub font: Font,
     pub cpu_frequency: u16,
     pub timer_frequency: u16,
 }
 
-impl Default for Config {
-    fn default() -> Self {
+impl Config {
+    // defaults of the given platform
+    pub fn for_profile(profile: Profile) -> Self {
         Self {
-            quircks: Quircks::default(),
+            profile,
+            quircks: profile.quircks(),
+            faults: profile.faults(),
+            memory_map: profile.memory_map(),
+            geometry: profile.geometry(),
+            keypad: profile.keypad(),
+            font: profile.font(),
             cpu_frequency: 500,
-            timer_frequency: 60,
+            timer_frequency: profile.timer_frequency(),
         }
     }
 }
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::for_profile(Profile::default())
+    }
+}
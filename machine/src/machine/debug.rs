@@ -1,5 +1,3 @@
-use crate::memory::Memory;
-
 use super::Machine;
 
 impl Machine {
@@ -7,11 +5,11 @@ impl Machine {
         let mut output = String::new();
 
         // print header
-        output.push_str(&format!("REG     | HEX    | BIN                | DEC\n"));
-        output.push_str(&format!("--------|--------|--------------------|----\n"));
+        output.push_str("REG     | HEX    | BIN                | DEC\n");
+        output.push_str("--------|--------|--------------------|----\n");
 
         // print common registers 0x0..=0xF
-        for i in (0..=0xF).into_iter() {
+        for i in 0..=0xF {
             let val = self.registers[i];
             output.push_str(&format!(
                 "0x{:X}\t| 0x{:04X} | 0b{:016b} | {}\n",
@@ -32,7 +30,7 @@ impl Machine {
 
     pub fn dump_memory_hex(&self, start: u16, length: u16) -> String {
         let mut output = String::new();
-        let data = self.memory.read_range(start, length);
+        let data = self.memory.read_range(start.into(), length.into());
 
         for (i, byte) in data.iter().enumerate() {
             if i % 16 == 0 {
@@ -0,0 +1,178 @@
+use crate::error::Error;
+
+// what the machine does when an instruction faults
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Policy {
+    // step fails with the error
+    Error,
+    // the faulting instruction is abandoned and execution continues
+    // with the next one, like a NOP
+    Ignore,
+    // addresses wrap around memory and the stack pointer around the stack.
+    // faults with nothing to wrap are ignored
+    Wrap,
+    // the machine stops with Status::Halted, step returns false
+    Halt,
+}
+
+impl Policy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::Error => "error",
+            Policy::Ignore => "ignore",
+            Policy::Wrap => "wrap",
+            Policy::Halt => "halt",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Policy::Error),
+            "ignore" => Some(Policy::Ignore),
+            "wrap" => Some(Policy::Wrap),
+            "halt" => Some(Policy::Halt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Faults {
+    // I set below the program area or beyond memory. Ignore still sets I,
+    // ROMs point it at the font or at tables below the program area
+    pub index: Policy,
+    // call with a full stack, return with an empty one
+    pub stack: Policy,
+    // opcodes that don't decode
+    pub instruction: Policy,
+    // reads and writes beyond memory
+    pub memory: Policy,
+    // PC outside the program area or unaligned
+    pub program_counter: Policy,
+}
+
+impl Faults {
+    // every fault is an error, useful to catch bugs in ROMs
+    pub const STRICT: Faults = Faults {
+        index: Policy::Error,
+        stack: Policy::Error,
+        instruction: Policy::Error,
+        memory: Policy::Error,
+        program_counter: Policy::Error,
+    };
+
+    // the policy that applies to an error raised by an instruction,
+    // errors that are not faults of the program always fail
+    pub fn policy(&self, error: &Error) -> Policy {
+        match error.cause() {
+            Error::InvalidIndexAddress(_) | Error::IndexOverflow(_) => self.index,
+            Error::StackOverflow | Error::StackUnderflow => self.stack,
+            Error::InvalidInstruction(_) | Error::NotImplementedYet(_) => self.instruction,
+            Error::MemoryOutOfBound(_) => self.memory,
+            Error::InvalidProgramCounter(_) | Error::UnalignedProgramCounter(_) => {
+                self.program_counter
+            }
+            _ => Policy::Error,
+        }
+    }
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self::STRICT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Faults, Policy};
+    use crate::error::Error;
+    use crate::instruction::Instruction::{self, *};
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::{Machine, Status};
+    use crate::program::Program;
+
+    fn machine(faults: Faults, program: &[Instruction]) -> Machine {
+        let mut config = Config::for_profile(Profile::Strict);
+        config.faults = faults;
+
+        let mut machine = Machine::with_config(config);
+        machine
+            .load_program(Program(program.to_vec()).into())
+            .unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_policies() {
+        let program = [
+            SetIndex(0x050),               // 0x200
+            SetImmediate { vx: 0, kk: 4 }, // 0x202
+            Call(0x206),                   // 0x204
+            Call(0x206),                   // 0x206
+        ];
+
+        let mut strict = machine(Faults::STRICT, &program);
+        assert!(matches!(
+            strict.step().unwrap_err().cause(),
+            Error::InvalidIndexAddress(0x050)
+        ));
+
+        let faults = Faults {
+            index: Policy::Ignore,
+            stack: Policy::Halt,
+            ..Faults::STRICT
+        };
+        let mut machine = machine(faults, &program);
+        for _ in 0..18 {
+            assert!(machine.step().unwrap());
+        }
+        assert_eq!(machine.get_index(), 0x050);
+        assert!(!machine.step().unwrap());
+        assert!(!machine.step().unwrap());
+        assert_eq!(machine.get_pc(), 0x208);
+
+        let Status::Halted(err) = machine.status() else {
+            panic!("machine is still running");
+        };
+        assert!(matches!(err.cause(), Error::StackOverflow));
+
+        machine.reset();
+        assert!(matches!(machine.status(), Status::Running));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let program = [
+            SetIndex(0xFFE),                 // 0x200
+            SetImmediate { vx: 0, kk: 0x2 }, // 0x202
+            StoreRegisters(1),               // 0x204
+            AddIndex(0),                     // 0x206
+            Jump(0xFFE),                     // 0x208
+        ];
+
+        let faults = Faults {
+            index: Policy::Wrap,
+            memory: Policy::Wrap,
+            program_counter: Policy::Wrap,
+            ..Faults::STRICT
+        };
+        let mut machine = machine(faults, &program);
+        for _ in 0..5 {
+            machine.step().unwrap();
+        }
+
+        // V1 was stored at 0x000, overwriting the font, and I wrapped to 0
+        let memory = machine.get_memory();
+        assert_eq!(memory.read(0xFFF).unwrap(), 0x00);
+        assert_eq!(memory.read(0xFFE).unwrap(), 0x02);
+        assert_eq!(memory.read(0x000).unwrap(), 0x00);
+        assert_eq!(machine.get_index(), 0x000);
+
+        // 0x02 0x00 at the end of memory is a Syscall, the PC wraps after it
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x1000);
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x002);
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::{Arc, Mutex};
+
+use super::Machine;
+use super::faults::Policy;
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    // instruction bytes read by step, one event per byte
+    Fetch,
+}
+
+// memory access seen by a hook. value is the byte read from memory
+// or about to be written, pc the address of the executing instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub access: Access,
+    pub addr: u32,
+    pub value: u8,
+    pub pc: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    // the read returns, or the write stores, this value instead.
+    // memory itself is left alone on reads and fetches.
+    Replace(u8),
+    // the instruction fails with Error::AccessVetoed
+    Veto,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+// hooks are shared between clones of a machine
+pub(super) type Hook = Arc<Mutex<dyn FnMut(&Event) -> Action + Send>>;
+
+impl Machine {
+    // registers a hook called on every memory access made by the interpreter.
+    // hooks run in registration order, each one sees the value replaced by
+    // the previous ones. while any hook is registered a native program is
+    // not used, so no access goes unnoticed.
+    // clones of the machine share its hooks, one closure state behind one
+    // mutex: clones stepped on other threads, e.g. in a Batch, take turns
+    // on it and their events interleave. register hooks on each clone
+    // instead when they need their own state.
+    pub fn add_hook<F>(&mut self, hook: F) -> HookId
+    where
+        F: FnMut(&Event) -> Action + Send + 'static,
+    {
+        let id = HookId(self.next_hook);
+        self.next_hook += 1;
+        self.hooks.push((id, Arc::new(Mutex::new(hook))));
+        id
+    }
+
+    // returns false if the hook was not registered
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|(hook, _)| *hook != id);
+        self.hooks.len() != len
+    }
+
+    pub(super) fn fetch(&mut self, addr: u16) -> Result<u16> {
+        let next = match self.config.faults.program_counter {
+            Policy::Wrap => ((addr as usize + 1) % self.memory.size()) as u16,
+            _ => addr.wrapping_add(1),
+        };
+
+        let high = self.memory.read(addr.into())?;
+        let low = self.memory.read(next.into())?;
+        if self.hooks.is_empty() {
+            return Ok(u16::from_be_bytes([high, low]));
+        }
+
+        let high = self.notify(Access::Fetch, addr.into(), high, addr)?;
+        let low = self.notify(Access::Fetch, next.into(), low, addr)?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
+    pub(super) fn read(&mut self, addr: u32) -> Result<u8> {
+        let addr = self.wrap(addr);
+        let value = self.memory.read(addr)?;
+        if self.hooks.is_empty() {
+            return Ok(value);
+        }
+
+        self.notify(Access::Read, addr, value, self.pc.wrapping_sub(2))
+    }
+
+    pub(super) fn read_into(&mut self, start: u32, out: &mut [u8]) -> Result<()> {
+        if self.hooks.is_empty()
+            && let Some(data) = self.memory.slice(start, out.len())
+        {
+            out.copy_from_slice(data);
+            return Ok(());
+        }
+
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = self.read(start.wrapping_add(i as u32))?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn write(&mut self, addr: u32, value: u8) -> Result<()> {
+        let addr = self.wrap(addr);
+        let value = match self.hooks.is_empty() {
+            true => value,
+            false => self.notify(Access::Write, addr, value, self.pc.wrapping_sub(2))?,
+        };
+
+        self.memory.write(addr, value)?;
+
+        // writes to a mapped framebuffer show up on the display
+        if let Some(base) = self.config.memory_map.framebuffer {
+            self.display
+                .write_byte(addr.wrapping_sub(base.into()) as usize, value);
+        }
+
+        Ok(())
+    }
+
+    // out of bounds addresses wrap around with the Wrap memory policy
+    fn wrap(&self, addr: u32) -> u32 {
+        match self.config.faults.memory {
+            Policy::Wrap => (addr as usize % self.memory.size()) as u32,
+            _ => addr,
+        }
+    }
+
+    fn notify(&self, access: Access, addr: u32, value: u8, pc: u16) -> Result<u8> {
+        let mut event = Event {
+            access,
+            addr,
+            value,
+            pc,
+        };
+
+        for (_, hook) in &self.hooks {
+            let mut hook = hook.lock().unwrap_or_else(|err| err.into_inner());
+            match hook(&event) {
+                Action::Allow => (),
+                Action::Replace(value) => event.value = value,
+                Action::Veto => return Err(Error::AccessVetoed { addr, pc }),
+            }
+        }
+
+        Ok(event.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Access, Action, Event};
+    use crate::error::Error;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_hooks() {
+        let program = Program(vec![
+            SetIndex(0x300),               // 0x200
+            SetImmediate { vx: 0, kk: 7 }, // 0x202
+            StoreRegisters(0),             // 0x204
+            LoadRegisters(1),              // 0x206
+            StoreRegisters(0),             // 0x208
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let watch = machine.add_hook(move |event| {
+            if event.access != Access::Fetch {
+                log.lock().unwrap().push(*event);
+            }
+            Action::Allow
+        });
+
+        // a peripheral mapped at 0x301 and a write protected 0x300
+        let mut writes = 0;
+        machine.add_hook(move |event| match (event.access, event.addr) {
+            (Access::Read, 0x301) => Action::Replace(0x42),
+            (Access::Write, 0x300) => {
+                writes += 1;
+                if writes > 1 {
+                    Action::Veto
+                } else {
+                    Action::Allow
+                }
+            }
+            _ => Action::Allow,
+        });
+
+        for _ in 0..4 {
+            machine.step().unwrap();
+        }
+
+        assert_eq!(&machine.get_registers()[..2], &[7, 0x42]);
+        assert_eq!(machine.get_memory().read(0x301).unwrap(), 0);
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[
+                event(Access::Write, 0x300, 7, 0x204),
+                event(Access::Read, 0x300, 7, 0x206),
+                event(Access::Read, 0x301, 0, 0x206),
+            ]
+        );
+
+        assert!(matches!(
+            machine.step().unwrap_err().cause(),
+            Error::AccessVetoed {
+                addr: 0x300,
+                pc: 0x208
+            }
+        ));
+
+        assert!(machine.remove_hook(watch));
+        assert!(!machine.remove_hook(watch));
+    }
+
+    #[test]
+    fn test_mapped_stack() {
+        let program = Program(vec![
+            Call(0x204), // 0x200
+            Jump(0x202), // 0x202
+            Return,      // 0x204
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::CosmacVip));
+        machine.load_program(program.into()).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        machine.add_hook(move |event| {
+            if event.access != Access::Fetch {
+                log.lock().unwrap().push(*event);
+            }
+            Action::Allow
+        });
+
+        machine.step().unwrap();
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x202);
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[
+                event(Access::Write, 0xECE, 0x02, 0x200),
+                event(Access::Write, 0xECF, 0x02, 0x200),
+                event(Access::Read, 0xECE, 0x02, 0x204),
+                event(Access::Read, 0xECF, 0x02, 0x204),
+            ]
+        );
+    }
+
+    fn event(access: Access, addr: u32, value: u8, pc: u16) -> Event {
+        Event {
+            access,
+            addr,
+            value,
+            pc,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// 0NNN on the COSMAC VIP calls a machine code subroutine at NNN. hybrid
+// ROMs use them for effects the interpreter can't do, so with the
+// machine_code quirk they run on a CDP1802 set up the way the VIP
+// interpreter leaves it:
+//
+//   R2  stack pointer, below the variables page
+//   R3  program counter, starting at NNN
+//   R4  return address, routines end with D4 (SEP R4)
+//   R5  CHIP-8 program counter
+//   R6  address of V0
+//   R8  delay timer in R8.1, sound timer in R8.0
+//   RA  I
+//   RB  display page
+//
+// V0-VF are mirrored to the end of the second highest RAM page, 0x0EF0
+// with 4 KiB, and the display to the mapped framebuffer. both are read
+// back on return.
+
+use super::Machine;
+use crate::error::Error;
+use crate::keyboard::Keyboard;
+use crate::memory::Memory;
+use crate::vip::cdp1802::{Bus, Cdp1802};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// instructions a routine may run before it is considered lost
+const STEP_LIMIT: u32 = 100_000;
+
+impl Machine {
+    pub(super) fn run_machine_code(&mut self, addr: u16) -> Result<()> {
+        // the variables page and the stack below it have to fit in memory
+        let size = self.memory.size().min(0x10000);
+        let too_small = || Error::MemoryOutOfBound(size as u32);
+        let top = (size.checked_sub(0x100).ok_or_else(too_small)?) as u16;
+        let variables = top.checked_sub(0x10).ok_or_else(too_small)?;
+        let stack = variables
+            .checked_sub(0x21 + 2 * self.sp as u16)
+            .ok_or_else(too_small)?;
+
+        for (i, &value) in self.registers.iter().enumerate() {
+            self.memory.write((variables + i as u16).into(), value)?;
+        }
+        self.sync_framebuffer();
+
+        let mut cpu = Cdp1802::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.r[2] = stack;
+        cpu.r[3] = addr;
+        cpu.r[5] = self.pc;
+        cpu.r[6] = variables;
+        cpu.r[8] = u16::from_be_bytes([self.dt, self.st]);
+        cpu.r[0xA] = self.index as u16;
+        cpu.r[0xB] = self.config.memory_map.framebuffer.unwrap_or(top);
+
+        let mut bus = MachineCodeBus {
+            memory: &mut self.memory,
+            keys: self.keys,
+            latch: 0,
+        };
+        let mut steps = 0;
+        while cpu.p != 4 {
+            // nothing raises an interrupt to wake the CPU from IDL here
+            if steps == STEP_LIMIT || cpu.idle {
+                return Err(Error::MachineCodeTimeout(addr));
+            }
+            cpu.step(&mut bus);
+            steps += 1;
+        }
+
+        for (i, register) in self.registers.iter_mut().enumerate() {
+            *register = self.memory.read((variables + i as u16).into())?;
+        }
+        [self.dt, self.st] = cpu.r[8].to_be_bytes();
+        self.index = cpu.r[0xA].into();
+        self.pc = cpu.r[5];
+
+        if let Some(base) = self.config.memory_map.framebuffer {
+            for i in 0..self.display.framebuffer().len() {
+                let value = self.memory.read(base as u32 + i as u32).unwrap_or(0);
+                self.display.write_byte(i, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct MachineCodeBus<'a> {
+    memory: &'a mut Memory,
+    keys: Keyboard,
+    latch: u8,
+}
+
+impl Bus for MachineCodeBus<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let addr = addr as usize % self.memory.size();
+        self.memory.read(addr as u32).unwrap_or(0)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize % self.memory.size();
+        let _ = self.memory.write(addr as u32, value);
+    }
+
+    // the keypad latch is the only device
+    fn output(&mut self, port: u8, value: u8) {
+        if port == 2 {
+            self.latch = value & 0xF;
+        }
+    }
+
+    fn input(&mut self, _: u8) -> u8 {
+        0
+    }
+
+    fn flag(&self, line: u8) -> bool {
+        line == 3 && self.keys.is_key_pressed(self.latch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::faults::Faults;
+
+    #[test]
+    fn test_machine_code() {
+        let mut rom = vec![0; 0x112];
+        rom[..6].copy_from_slice(&[
+            0x61, 0x21, // V1 = 0x21
+            0x03, 0x00, // machine code at 0x300
+            0x03, 0x10, // machine code at 0x310
+        ]);
+        rom[0x100..].copy_from_slice(&[
+            0x16, 0x06, 0xFC, 0x01, 0x56, // V1 += 1
+            0x9B, 0xBF, 0xF8, 0x00, 0xAF, // RF = display page
+            0xF8, 0xFF, 0x5F, // light the first 8 pixels
+            0xD4, // return
+            0x00, 0x00, // padding
+            0x30, 0x10, // never returns
+        ]);
+
+        let mut config = Config::for_profile(Profile::CosmacVip);
+        config.quircks.machine_code = true;
+
+        let mut machine = Machine::with_config(config.clone());
+        machine.load_rom(&rom).unwrap();
+        machine.step().unwrap();
+        machine.step().unwrap();
+
+        assert_eq!(machine.get_registers()[1], 0x22);
+        assert_eq!(machine.get_pc(), 0x204);
+        assert!(machine.get_display().get_pixel(7, 0));
+
+        let error = machine.step().unwrap_err();
+        assert!(matches!(error.cause(), Error::MachineCodeTimeout(0x310)));
+
+        // 0000 lands on IDL at 0x000, which nothing wakes up from
+        let mut machine = Machine::with_config(config.clone());
+        machine.load_rom(&[0x00, 0x00]).unwrap();
+        let error = machine.step().unwrap_err();
+        assert!(matches!(error.cause(), Error::MachineCodeTimeout(0x000)));
+
+        // without the quirk 0NNN is ignored
+        let mut machine = Machine::with_config(Config::for_profile(Profile::CosmacVip));
+        machine.load_rom(&[0x00, 0x00]).unwrap();
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x202);
+    }
+
+    #[test]
+    fn test_small_memory() {
+        let mut config = Config::for_profile(Profile::CosmacVip);
+        config.quircks.machine_code = true;
+        config.memory_map.size = 0x120;
+        config.memory_map.program_start = 0x100;
+        config.memory_map.stack = None;
+        config.memory_map.framebuffer = None;
+        config.faults = Faults::STRICT;
+
+        let mut machine = Machine::with_config(config);
+        machine.load_program(vec![0x0100]).unwrap();
+        let error = machine.step().unwrap_err();
+        assert!(matches!(error.cause(), Error::MemoryOutOfBound(0x120)));
+    }
+}
@@ -0,0 +1,330 @@
+use super::Machine;
+use crate::display::Display;
+use crate::instruction::Instruction;
+use crate::keyboard::Keyboard;
+use crate::memory::Memory;
+
+// entry point of a recompiled program, see recompiler::recompile
+pub type NativeProgram = fn(&mut Context<'_>) -> Exit;
+
+// reason why native code handed control back to the machine
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exit {
+    // instruction budget is spent, PC points to the next instruction
+    Budget,
+    // PC points to an instruction that has to be interpreted: computed jumps,
+    // modified code, code that was never recompiled or an instruction that failed
+    Interpret,
+    // FX0A is waiting for a key press, PC points to it
+    Wait,
+}
+
+// Context is what recompiled code runs against. it exposes the machine state
+// and falls back to the interpreter handlers for the heavier instructions
+// so both execution paths share the same semantics.
+pub struct Context<'a> {
+    machine: &'a mut Machine,
+    budget: u32,
+}
+
+impl<'a> Context<'a> {
+    pub(super) fn new(machine: &'a mut Machine, budget: u32) -> Self {
+        Self { machine, budget }
+    }
+
+    // number of instructions native code is still allowed to execute
+    pub fn budget(&self) -> u32 {
+        self.budget
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.machine.pc
+    }
+
+    pub fn jump(&mut self, addr: u16) {
+        self.machine.pc = addr;
+    }
+
+    pub fn v(&self, x: u8) -> u8 {
+        self.machine.registers[x as usize]
+    }
+
+    pub fn set_v(&mut self, x: u8, value: u8) {
+        self.machine.registers[x as usize] = value;
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.machine.dt
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.machine.dt = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.machine.st = value;
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.machine.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.machine.memory
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.machine.display
+    }
+
+    pub fn keys(&self) -> &Keyboard {
+        &self.machine.keys
+    }
+
+    // called at the top of every recompiled block. takes the whole block
+    // from the budget and makes sure the code in memory is still the code
+    // that was compiled, so self-modifying programs drop to the interpreter.
+    pub fn enter(&mut self, addr: u16, code: &[u8]) -> Result<(), Exit> {
+        let length = code.len() as u32 / 2;
+        if self.budget < length {
+            return Err(Exit::Budget);
+        }
+
+        if self.machine.memory.slice(addr.into(), code.len()) != Some(code) {
+            return Err(Exit::Interpret);
+        }
+
+        self.budget -= length;
+        Ok(())
+    }
+
+    // leaves the block at `addr`, giving back the budget taken for the
+    // `unexecuted` instructions starting from this one
+    pub fn leave(&mut self, addr: u16, unexecuted: u32, exit: Exit) -> Exit {
+        self.machine.pc = addr;
+        self.budget += unexecuted;
+        exit
+    }
+
+    // runs a single instruction at `addr` through the interpreter handler.
+    // on failure it leaves the block, so the interpreter re-runs the
+    // instruction and reports the error itself.
+    pub fn exec(&mut self, addr: u16, unexecuted: u32, inst: Instruction) -> Result<(), Exit> {
+        self.machine.pc = addr + 2;
+
+        match self.machine.exec(inst) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.leave(addr, unexecuted, Exit::Interpret)),
+        }
+    }
+
+    pub fn wait_for_key(&mut self, addr: u16, unexecuted: u32, x: u8) -> Result<(), Exit> {
+        match self.machine.keys.get_first_pressed_key() {
+            Some(key) => {
+                self.set_v(x, key);
+                Ok(())
+            }
+            None => Err(self.leave(addr, unexecuted, Exit::Wait)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Context, Exit};
+    use crate::coverage::Coverage;
+    use crate::error::Error;
+    use crate::instruction::Instruction;
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::faults::Policy;
+    use crate::machine::{Machine, Status};
+    use crate::profiler::Profiler;
+
+    // hand-written equivalent of what the recompiler emits for a tiny loop
+    fn program(ctx: &mut Context<'_>) -> Exit {
+        loop {
+            let result = match ctx.pc() {
+                0x0200 => block_0200(ctx),
+                _ => Err(Exit::Interpret),
+            };
+
+            if let Err(exit) = result {
+                return exit;
+            }
+        }
+    }
+
+    fn block_0200(ctx: &mut Context<'_>) -> Result<(), Exit> {
+        ctx.enter(0x0200, &[0x70, 0x01, 0x12, 0x00])?;
+        ctx.set_v(0x0, ctx.v(0x0).wrapping_add(0x01));
+        ctx.jump(0x0200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_budget() {
+        let program_words = vec![
+            Instruction::AddImmediate { vx: 0, kk: 1 }.encode(),
+            Instruction::Jump(0x200).encode(),
+        ];
+
+        let mut machine = Machine::new();
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(program)).unwrap();
+
+        // 2 full blocks natively, the last instruction is interpreted
+        machine.run_instructions(5).unwrap();
+        assert_eq!(machine.get_registers()[0], 3);
+        assert_eq!(machine.get_pc(), 0x202);
+        assert_eq!(machine.cycles, 5);
+    }
+
+    // blocks as emitted for a store that patches the instruction after it
+    fn patching_program(ctx: &mut Context<'_>) -> Exit {
+        loop {
+            let result = match ctx.pc() {
+                0x0200 => block_patch_0200(ctx),
+                0x0206 => block_patch_0206(ctx),
+                _ => Err(Exit::Interpret),
+            };
+
+            if let Err(exit) = result {
+                return exit;
+            }
+        }
+    }
+
+    fn block_patch_0200(ctx: &mut Context<'_>) -> Result<(), Exit> {
+        ctx.enter(0x0200, &[0xA2, 0x06, 0x60, 0x72, 0xF0, 0x55])?;
+        ctx.exec(0x0200, 3, Instruction::SetIndex(0x206))?;
+        ctx.set_v(0x0, 0x72);
+        ctx.exec(0x0204, 1, Instruction::StoreRegisters(0))?;
+        ctx.jump(0x0206);
+        Ok(())
+    }
+
+    fn block_patch_0206(ctx: &mut Context<'_>) -> Result<(), Exit> {
+        ctx.enter(0x0206, &[0x71, 0x02, 0x12, 0x08])?;
+        ctx.set_v(0x1, ctx.v(0x1).wrapping_add(0x02));
+        ctx.jump(0x0208);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_patches_next_instruction() {
+        let program_words = vec![
+            Instruction::SetIndex(0x206).encode(),
+            Instruction::SetImmediate { vx: 0, kk: 0x72 }.encode(),
+            Instruction::StoreRegisters(0).encode(),
+            Instruction::AddImmediate { vx: 1, kk: 2 }.encode(),
+            Instruction::Jump(0x208).encode(),
+        ];
+
+        let mut machine = Machine::new();
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(patching_program)).unwrap();
+
+        // F055 turns 7102 into 7202, which has to run in the interpreter
+        machine.run_instructions(5).unwrap();
+        assert_eq!(machine.get_registers()[1], 0);
+        assert_eq!(machine.get_registers()[2], 2);
+    }
+
+    #[test]
+    fn test_self_modified_code() {
+        let program_words = vec![
+            Instruction::AddImmediate { vx: 0, kk: 1 }.encode(),
+            Instruction::Jump(0x200).encode(),
+        ];
+
+        let mut machine = Machine::new();
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(program)).unwrap();
+
+        // turn 7001 into 7002, the block no longer matches
+        machine.memory.write(0x201, 0x02).unwrap();
+        machine.run_instructions(4).unwrap();
+        assert_eq!(machine.get_registers()[0], 4);
+    }
+
+    #[test]
+    fn test_halted_machine() {
+        let program_words = vec![
+            Instruction::AddImmediate { vx: 0, kk: 1 }.encode(),
+            Instruction::Jump(0x200).encode(),
+            Instruction::Return.encode(),
+        ];
+
+        let mut config = Config::default();
+        config.faults.stack = Policy::Halt;
+        let mut machine = Machine::with_config(config);
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(program)).unwrap();
+
+        // the return at 0x204 halts the machine, the native loop must not
+        // run afterwards
+        machine.pc = 0x204;
+        assert!(!machine.step().unwrap());
+        assert!(matches!(machine.status(), Status::Halted(_)));
+        machine.pc = 0x200;
+
+        assert!(!machine.step_frame().unwrap());
+        assert_eq!(machine.get_registers()[0], 0);
+        assert_eq!(machine.get_pc(), 0x200);
+    }
+
+    #[test]
+    fn test_profiler_sees_native_code() {
+        let program_words = vec![
+            Instruction::AddImmediate { vx: 0, kk: 1 }.encode(),
+            Instruction::Jump(0x200).encode(),
+        ];
+
+        let mut machine = Machine::new();
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(program)).unwrap();
+        machine.set_profiler(Some(Profiler::new()));
+
+        machine.run_instructions(6).unwrap();
+        assert_eq!(machine.get_registers()[0], 3);
+        assert_eq!(machine.profiler().unwrap().total(), 6);
+    }
+
+    #[test]
+    fn test_coverage_sees_native_code() {
+        let program_words = vec![
+            Instruction::AddImmediate { vx: 0, kk: 1 }.encode(),
+            Instruction::Jump(0x200).encode(),
+        ];
+
+        let mut machine = Machine::new();
+        machine.load_program(program_words).unwrap();
+        machine.set_native(Some(program)).unwrap();
+        machine.set_coverage(Some(Coverage::new(machine.get_memory().size())));
+
+        machine.run_instructions(4).unwrap();
+        let coverage = machine.coverage().unwrap();
+        for addr in 0x200..0x204 {
+            assert_eq!(coverage.flags(addr), Coverage::EXECUTED);
+        }
+    }
+
+    #[test]
+    fn test_other_dialects() {
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        assert!(matches!(
+            machine.set_native(Some(program)),
+            Err(Error::InvalidNative(_))
+        ));
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Eti660));
+        assert!(matches!(
+            machine.set_native(Some(program)),
+            Err(Error::InvalidNative(_))
+        ));
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::CosmacVip));
+        machine.set_native(Some(program)).unwrap();
+        machine.set_native(None).unwrap();
+    }
+}
@@ -21,6 +21,17 @@ impl Machine {
         Ok(())
     }
 
+    // CHIP-8X 5XY1, the nibbles are added separately and never carry
+    pub(super) fn op_add_nibbles(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let x = self.registers[vx as usize];
+        let y = self.registers[vy as usize];
+
+        let high = (x & 0xF0).wrapping_add(y & 0xF0);
+        let low = (x & 0x0F).wrapping_add(y & 0x0F) & 0x0F;
+        self.registers[vx as usize] = high | low;
+        Ok(())
+    }
+
     pub(super) fn op_subtract(&mut self, vx: u8, vy: u8) -> Result<()> {
         let x = self.registers[vx as usize];
         let y = self.registers[vy as usize];
@@ -1,23 +1,45 @@
 use super::Machine;
+use super::faults::Policy;
 use crate::error::Error;
 
 type Result<T> = std::result::Result<T, Error>;
 
 impl Machine {
+    // a mapped stack is read back from memory, programs may have changed it.
+    // it is accessed like any other memory, hooks and faults apply.
     pub(super) fn op_return(&mut self) -> Result<()> {
         if self.sp == 0 {
-            return Err(Error::StackUnderflow);
+            if self.config.faults.stack != Policy::Wrap || self.stack.is_empty() {
+                return Err(Error::StackUnderflow);
+            }
+            self.sp = self.stack.len() as u8;
         }
 
-        self.pc = self.stack[self.sp as usize];
         self.sp -= 1;
+        self.pc = match self.config.memory_map.stack_entry(self.sp) {
+            Some(entry) => {
+                let high = self.read(entry.into())?;
+                let low = self.read(u32::from(entry) + 1)?;
+                u16::from_be_bytes([high, low])
+            }
+            None => self.stack[self.sp as usize],
+        };
 
         Ok(())
     }
 
     pub(super) fn op_call(&mut self, addr: u16) -> Result<()> {
-        if self.sp == 0xF {
-            return Err(Error::StackOverflow);
+        if self.sp as usize == self.stack.len() {
+            if self.config.faults.stack != Policy::Wrap || self.stack.is_empty() {
+                return Err(Error::StackOverflow);
+            }
+            self.sp = 0;
+        }
+
+        if let Some(entry) = self.config.memory_map.stack_entry(self.sp) {
+            let [high, low] = self.pc.to_be_bytes();
+            self.write(entry.into(), high)?;
+            self.write(u32::from(entry) + 1, low)?;
         }
 
         self.stack[self.sp as usize] = self.pc;
@@ -27,6 +49,12 @@ impl Machine {
         Ok(())
     }
 
+    // skips the next instruction. the PC wraps at the end of a 64 KiB map,
+    // the next fetch then faults like any other invalid PC
+    pub(super) fn skip(&mut self) {
+        self.pc = self.pc.wrapping_add(2);
+    }
+
     pub(super) fn op_jump(&mut self, addr: u16) -> Result<()> {
         self.pc = addr;
         Ok(())
@@ -40,7 +68,7 @@ impl Machine {
 
     pub(super) fn op_skip_if_equal_imm(&mut self, vx: u8, kk: u8) -> Result<()> {
         if self.registers[vx as usize] == kk {
-            self.pc += 2;
+            self.skip();
         }
 
         Ok(())
@@ -48,14 +76,14 @@ impl Machine {
 
     pub(super) fn op_skip_if_not_equal_imm(&mut self, vx: u8, kk: u8) -> Result<()> {
         if self.registers[vx as usize] != kk {
-            self.pc += 2;
+            self.skip();
         }
         Ok(())
     }
 
     pub(super) fn op_skip_if_equal(&mut self, vx: u8, vy: u8) -> Result<()> {
         if self.registers[vx as usize] == self.registers[vy as usize] {
-            self.pc += 2;
+            self.skip();
         }
 
         Ok(())
@@ -63,9 +91,98 @@ impl Machine {
 
     pub(super) fn op_skip_if_not_equal(&mut self, vx: u8, vy: u8) -> Result<()> {
         if self.registers[vx as usize] != self.registers[vy as usize] {
-            self.pc += 2;
+            self.skip();
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn op_skip_if_greater(&mut self, vx: u8, vy: u8) -> Result<()> {
+        if self.registers[vx as usize] > self.registers[vy as usize] {
+            self.skip();
         }
 
         Ok(())
     }
+
+    // relative to the next instruction
+    pub(super) fn op_jump_relative(&mut self, offset: i16) -> Result<()> {
+        self.pc = self.pc.wrapping_add_signed(offset);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::{Machine, Status};
+    use crate::program::Program;
+
+    #[test]
+    fn test_call_and_return() {
+        let program = Program(vec![
+            Call(0x206),                   // 0x200
+            SetImmediate { vx: 0, kk: 1 }, // 0x202
+            Jump(0x204),                   // 0x204
+            Call(0x20A),                   // 0x206
+            Return,                        // 0x208
+            Return,                        // 0x20A
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+
+        machine.step().unwrap();
+        machine.step().unwrap();
+        assert_eq!((machine.get_pc(), machine.get_sp()), (0x20A, 2));
+
+        // returns pop the address pushed by the matching call
+        machine.step().unwrap();
+        assert_eq!((machine.get_pc(), machine.get_sp()), (0x208, 1));
+        machine.step().unwrap();
+        assert_eq!((machine.get_pc(), machine.get_sp()), (0x202, 0));
+
+        machine.step().unwrap();
+        assert_eq!(machine.get_registers()[0], 1);
+    }
+
+    #[test]
+    fn test_chip8e_jumps() {
+        let program = Program(vec![
+            SetImmediate { vx: 4, kk: 0x33 },
+            SetImmediate { vx: 5, kk: 0x22 },
+            // 0x204
+            SkipIfGreater { vx: 4, vy: 5 },
+            JumpForward(2),
+            JumpBack(4),
+            Stop,
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..5 {
+            machine.step().unwrap();
+        }
+
+        // V4 > V5 skipped to the backward jump, which went back to the
+        // forward jump over it
+        assert_eq!(machine.get_pc(), 0x20A);
+
+        assert!(!machine.step().unwrap());
+        assert!(matches!(machine.status(), Status::Stopped));
+        assert!(!machine.step().unwrap());
+    }
+
+    #[test]
+    fn test_skip_at_end_of_memory() {
+        let mut machine = Machine::with_config(Config::for_profile(Profile::MegaChip));
+        machine.load_program(vec![]).unwrap();
+        machine.memory.write_word(0xFFFC, 0x3000).unwrap();
+        machine.pc = 0xFFFC;
+
+        // 3000 skips the last instruction and wraps the PC
+        assert!(machine.step().unwrap());
+        assert_eq!(machine.get_pc(), 0x0000);
+    }
 }
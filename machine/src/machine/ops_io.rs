@@ -1,4 +1,5 @@
 use super::Machine;
+use crate::display::Rect;
 use crate::error::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -7,7 +8,7 @@ impl Machine {
     pub(super) fn op_skip_if_key(&mut self, vx: u8) -> Result<()> {
         let key = self.registers[vx as usize];
         if self.keys.is_key_pressed(key) {
-            self.pc += 2;
+            self.skip();
         }
 
         Ok(())
@@ -16,7 +17,7 @@ impl Machine {
     pub(super) fn op_skip_if_not_key(&mut self, vx: u8) -> Result<()> {
         let key = self.registers[vx as usize];
         if !self.keys.is_key_pressed(key) {
-            self.pc += 2;
+            self.skip();
         }
 
         Ok(())
@@ -36,12 +37,194 @@ impl Machine {
     }
 
     pub(super) fn op_draw(&mut self, vx: u8, vy: u8, n: u8) -> Result<()> {
-        let sprite = self.memory.read_range(self.index, n as u16);
         let x = self.registers[vx as usize];
         let y = self.registers[vy as usize];
 
-        let collision = self.display.draw_sprite(x, y, &sprite);
+        if self.megachip.as_ref().is_some_and(|mega| mega.is_enabled()) {
+            return self.op_draw_megachip(x, y);
+        }
+
+        // hooks see every byte read, without them the sprite is borrowed
+        let collision = match self.memory.slice(self.index, n as usize) {
+            Some(sprite) if self.hooks.is_empty() => self.display.draw_sprite(x, y, sprite),
+            _ => {
+                let mut sprite = [0; 16];
+                let sprite = &mut sprite[..n as usize];
+                self.read_into(self.index, sprite)?;
+                self.display.draw_sprite(x, y, sprite)
+            }
+        };
         self.registers[0xF] = collision as u8;
+        self.sync_framebuffer();
+        Ok(())
+    }
+
+    pub(super) fn op_cycle_background(&mut self) -> Result<()> {
+        self.display.cycle_background();
+        Ok(())
+    }
+
+    // Vx holds the left zone in its low nibble and the number of extra zones
+    // to the right in the high one, Vx+1 the same vertically. zones are 8x4.
+    pub(super) fn op_set_color_zones(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let x = self.registers[vx as usize];
+        let y = self.registers[(vx as usize + 1) % 16];
+
+        let area = Rect {
+            x: (x & 0x0F) * 8,
+            y: (y & 0x0F) * 4,
+            width: ((x >> 4) + 1) * 8,
+            height: ((y >> 4) + 1) * 4,
+        };
+        self.display.set_color(area, self.registers[vy as usize]);
+        Ok(())
+    }
+
+    pub(super) fn op_set_color_rows(&mut self, vx: u8, vy: u8, n: u8) -> Result<()> {
+        let area = Rect {
+            x: self.registers[vx as usize],
+            y: self.registers[(vx as usize + 1) % 16],
+            width: 1,
+            height: n,
+        };
+        self.display.set_color(area, self.registers[vy as usize]);
+        Ok(())
+    }
+
+    pub(super) fn op_skip_if_key2(&mut self, vx: u8) -> Result<()> {
+        let key = self.registers[vx as usize];
+        if self.keys2.is_key_pressed(key) {
+            self.skip();
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn op_skip_if_not_key2(&mut self, vx: u8) -> Result<()> {
+        let key = self.registers[vx as usize];
+        if !self.keys2.is_key_pressed(key) {
+            self.skip();
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn op_output_port(&mut self, vx: u8) -> Result<()> {
+        self.output_port = self.registers[vx as usize];
+        self.output_written = true;
+        Ok(())
+    }
+
+    // the port is sampled once per frame, there is nothing to wait for
+    pub(super) fn op_input_port(&mut self, vx: u8) -> Result<()> {
+        self.registers[vx as usize] = self.input_port;
         Ok(())
     }
+
+    // repeats until a new value arrived on the port
+    pub(super) fn op_wait_input_port(&mut self, vx: u8) -> Result<()> {
+        if !self.input_strobe {
+            self.pc -= 2;
+            return Ok(());
+        }
+
+        self.input_strobe = false;
+        self.op_input_port(vx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::keyboard::Keyboard;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_chip8x() {
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 0x39 },
+            SetImmediate { vx: 1, kk: 0xEC },
+            AddNibbles { vx: 0, vy: 1 },
+            // zones 2 and 3 of the second zone row
+            SetImmediate { vx: 2, kk: 0x12 },
+            SetImmediate { vx: 3, kk: 0x01 },
+            SetImmediate { vx: 4, kk: 0x05 },
+            SetColorZones { vx: 2, vy: 4 },
+            CycleBackground,
+            SetImmediate { vx: 5, kk: 0x03 },
+            SkipIfKey2(5),
+            SetImmediate { vx: 6, kk: 0xFF },
+            OutputPort(0),
+            InputPort(7),
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8X));
+        machine.load_program(program.into()).unwrap();
+        assert_eq!(machine.get_pc(), 0x300);
+
+        machine.set_second_keys(Keyboard::from(1 << 3));
+        machine.set_input_port(0x42);
+        while machine.get_pc() < 0x300 + 13 * 2 {
+            machine.step().unwrap();
+        }
+
+        let registers = machine.get_registers();
+        assert_eq!(registers[0], 0x15);
+        assert_eq!(registers[6], 0x00);
+        assert_eq!(registers[7], 0x42);
+        assert_eq!(machine.get_output_port(), 0x15);
+
+        let display = machine.get_display();
+        assert_eq!(display.background(), 1);
+        assert_eq!(display.color(15, 4), 1);
+        assert_eq!(display.color(16, 4), 5);
+        assert_eq!(display.color(31, 7), 5);
+        assert_eq!(display.color(32, 7), 1);
+        assert_eq!(display.color(16, 8), 1);
+    }
+
+    #[test]
+    fn test_eti660_and_dream6800() {
+        let program: Vec<u16> = Program(vec![
+            SetImmediate { vx: 0, kk: 0x5 },
+            SkipIfKey(0),
+            SetImmediate { vx: 1, kk: 0xFF },
+        ])
+        .into();
+
+        let config = Config::for_profile(Profile::Eti660);
+        assert_eq!(config.timer_frequency, 50);
+        assert_eq!(config.keypad.key(3, 0), Some(0x0));
+        let mut machine = Machine::with_config(config);
+        machine.load_program(program.clone()).unwrap();
+        assert_eq!(machine.get_pc(), 0x600);
+        assert_eq!(machine.get_display().height(), 48);
+
+        // the DREAM 6800 only sees the lowest key of 3 and 5
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Dream6800));
+        machine.load_program(program).unwrap();
+        machine.set_keys(Keyboard::from(1 << 3 | 1 << 5));
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+        assert_eq!(machine.get_registers()[1], 0xFF);
+    }
+
+    #[test]
+    fn test_chip8e_ports() {
+        let program = Program(vec![WaitInputPort3(7), OutputPort3(7)]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_program(program.into()).unwrap();
+
+        // FXE3 waits for a value on the port
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x200);
+        machine.set_input_port(0x5A);
+        machine.step().unwrap();
+        machine.step().unwrap();
+        assert_eq!(machine.get_output_port(), 0x5A);
+    }
 }
@@ -0,0 +1,169 @@
+use super::Machine;
+use crate::error::Error;
+use crate::instruction::Instruction;
+use crate::megachip::{Blend, MegaChip, Sample};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// MegaChip instructions
+impl Machine {
+    pub(super) fn op_mega_mode(&mut self, enabled: bool) -> Result<()> {
+        self.megachip().set_enabled(enabled);
+        Ok(())
+    }
+
+    // the low 16 bits of the address follow the instruction
+    pub(super) fn op_long_index(&mut self, nn: u8) -> Result<()> {
+        let low = self.fetch(self.pc)?;
+        self.pc = self.pc.wrapping_add(2);
+        self.op_set_index((nn as u32) << 16 | low as u32)
+    }
+
+    pub(super) fn op_load_palette(&mut self, nn: u8) -> Result<()> {
+        let mut colors = [0; 255 * 4];
+        let colors = &mut colors[..nn as usize * 4];
+        self.read_into(self.index, colors)?;
+        self.megachip().load_palette(colors);
+        Ok(())
+    }
+
+    pub(super) fn op_sprite_width(&mut self, nn: u8) -> Result<()> {
+        self.megachip().set_sprite_width(nn);
+        Ok(())
+    }
+
+    pub(super) fn op_sprite_height(&mut self, nn: u8) -> Result<()> {
+        self.megachip().set_sprite_height(nn);
+        Ok(())
+    }
+
+    pub(super) fn op_screen_alpha(&mut self, nn: u8) -> Result<()> {
+        self.megachip().set_alpha(nn);
+        Ok(())
+    }
+
+    pub(super) fn op_play_sample(&mut self, n: u8) -> Result<()> {
+        let mut header = [0; Sample::HEADER_SIZE];
+        self.read_into(self.index, &mut header)?;
+
+        let rate = u16::from_be_bytes([header[0], header[1]]);
+        let length = u32::from_be_bytes([0, header[2], header[3], header[4]]);
+        let mut data = vec![0; length as usize];
+        self.read_into(self.index + Sample::HEADER_SIZE as u32, &mut data)?;
+
+        self.megachip().set_sample(Some(Sample {
+            rate,
+            data,
+            looping: n == 0,
+        }));
+        self.sample_changed = true;
+        Ok(())
+    }
+
+    pub(super) fn op_stop_sample(&mut self) -> Result<()> {
+        self.megachip().set_sample(None);
+        self.sample_changed = true;
+        Ok(())
+    }
+
+    pub(super) fn op_blend_mode(&mut self, n: u8) -> Result<()> {
+        let blend = Blend::from_code(n).ok_or(Error::InvalidInstruction(
+            Instruction::BlendMode(n).encode(),
+        ))?;
+        self.megachip().set_blend(blend);
+        Ok(())
+    }
+
+    pub(super) fn op_collision_color(&mut self, nn: u8) -> Result<()> {
+        self.megachip().set_collision_color(nn);
+        Ok(())
+    }
+
+    // DXYN in MegaChip mode, N is ignored and the sprite size is used
+    pub(super) fn op_draw_megachip(&mut self, x: u8, y: u8) -> Result<()> {
+        let (width, height) = self.megachip().sprite_size();
+        let length = width * height;
+
+        let collision = match self.memory.slice(self.index, length) {
+            Some(sprite) if self.hooks.is_empty() => {
+                let megachip = self.megachip.get_or_insert_default();
+                megachip.draw_sprite(x, y, sprite)
+            }
+            _ => {
+                let mut sprite = vec![0; length];
+                self.read_into(self.index, &mut sprite)?;
+                self.megachip().draw_sprite(x, y, &sprite)
+            }
+        };
+        self.registers[0xF] = collision as u8;
+        Ok(())
+    }
+
+    // bytes DXYN reads from memory, the sprite size in MegaChip mode
+    pub(super) fn sprite_length(&self, n: u8) -> usize {
+        match self.megachip.as_deref().filter(|mega| mega.is_enabled()) {
+            Some(megachip) => {
+                let (width, height) = megachip.sprite_size();
+                width * height
+            }
+            None => n as usize,
+        }
+    }
+
+    fn megachip(&mut self) -> &mut MegaChip {
+        self.megachip.get_or_insert_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+
+    #[test]
+    fn test_megachip() {
+        let program: Vec<u16> = [
+            MegaOn.into(),
+            LongIndex(0x00).into(),
+            0x0220,
+            LoadPalette(1).into(),
+            SpriteWidth(2).into(),
+            SpriteHeight(1).into(),
+            SetImmediate { vx: 0, kk: 4 }.into(),
+            LongIndex(0x00).into(),
+            0x0224,
+            Draw { vx: 0, vy: 1, n: 0 }.into(),
+            Clear.into(),
+            0x0000,
+            0x0000,
+            0x0000,
+            0x0000,
+            0x0000,
+            // palette entry 1, then a 2x1 sprite
+            0xFFFF,
+            0x0000,
+            0x0101,
+        ]
+        .into();
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::MegaChip));
+        machine.load_program(program).unwrap();
+        while machine.get_pc() < 0x216 {
+            machine.step().unwrap();
+        }
+
+        let megachip = machine.get_megachip().unwrap();
+        assert!(megachip.is_enabled());
+        assert_eq!(
+            megachip.frame()[3..7],
+            [
+                [0x00, 0x00, 0x00, 0xFF],
+                [0xFF, 0x00, 0x00, 0xFF],
+                [0xFF, 0x00, 0x00, 0xFF],
+                [0x00, 0x00, 0x00, 0xFF]
+            ]
+        );
+        assert_eq!(machine.get_registers()[0xF], 0);
+    }
+}
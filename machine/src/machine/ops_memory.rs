@@ -1,5 +1,3 @@
-use std::iter;
-
 use super::Machine;
 use crate::error::Error;
 
@@ -8,7 +6,8 @@ type Result<T> = std::result::Result<T, Error>;
 impl Machine {
     pub(super) fn op_load_font(&mut self, vx: u8) -> Result<()> {
         let digit = self.registers[vx as usize];
-        self.index = 0x50 + (digit as u16 * 5);
+        let base = self.config.memory_map.font_base;
+        self.index = self.config.font.small_glyph(base, digit).into();
         Ok(())
     }
 
@@ -18,9 +17,9 @@ impl Machine {
         let tens = value / 10 % 10;
         let ones = value % 10;
 
-        self.memory.write(self.index, hundreds)?;
-        self.memory.write(self.index + 1, tens)?;
-        self.memory.write(self.index + 2, ones)?;
+        self.write(self.index, hundreds)?;
+        self.write(self.index + 1, tens)?;
+        self.write(self.index + 2, ones)?;
 
         Ok(())
     }
@@ -28,8 +27,8 @@ impl Machine {
     pub(super) fn op_store_registers(&mut self, x: u8) -> Result<()> {
         for i in 0..=x {
             let value = self.registers[i as usize];
-            let addr = self.index + i as u16;
-            self.memory.write(addr, value)?;
+            let addr = self.index + i as u32;
+            self.write(addr, value)?;
         }
 
         Ok(())
@@ -37,11 +36,72 @@ impl Machine {
 
     pub(super) fn op_load_registers(&mut self, x: u8) -> Result<()> {
         for i in 0..=x {
-            let addr = self.index + i as u16;
-            let value = self.memory.read(addr)?;
+            let addr = self.index + i as u32;
+            let value = self.read(addr)?;
             self.registers[i as usize] = value;
         }
 
         Ok(())
     }
+
+    pub(super) fn op_store_range(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let count = vx.abs_diff(vy) as u32 + 1;
+        for i in 0..count {
+            let value = self.registers[Self::range_register(vx, vy, i)];
+            self.write(self.index + i, value)?;
+        }
+
+        self.index += count;
+        Ok(())
+    }
+
+    pub(super) fn op_load_range(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let count = vx.abs_diff(vy) as u32 + 1;
+        for i in 0..count {
+            let value = self.read(self.index + i)?;
+            self.registers[Self::range_register(vx, vy, i)] = value;
+        }
+
+        self.index += count;
+        Ok(())
+    }
+
+    // i-th register from Vx to Vy, counting down when Y is below X
+    fn range_register(vx: u8, vy: u8, i: u32) -> usize {
+        match vy >= vx {
+            true => vx as usize + i as usize,
+            false => vx as usize - i as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_chip8e_ranges() {
+        let program = Program(vec![
+            SetImmediate { vx: 1, kk: 0x11 },
+            SetImmediate { vx: 2, kk: 0x22 },
+            SetImmediate { vx: 3, kk: 0x33 },
+            SetIndex(0x300),
+            StoreRange { vx: 3, vy: 1 },
+            SetIndex(0x300),
+            LoadRange { vx: 4, vy: 6 },
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..7 {
+            machine.step().unwrap();
+        }
+
+        // V3, V2, V1 stored and loaded into V4, V5, V6
+        assert_eq!(machine.get_registers()[4..7], [0x33, 0x22, 0x11]);
+        assert_eq!(machine.get_index(), 0x303);
+    }
 }
@@ -1,4 +1,5 @@
 use super::Machine;
+use super::faults::Policy;
 use crate::error::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -14,10 +15,24 @@ impl Machine {
         Ok(())
     }
 
-    pub(super) fn op_set_index(&mut self, addr: u16) -> Result<()> {
-        if addr < 0x200 {
+    pub(super) fn op_set_index(&mut self, addr: u32) -> Result<()> {
+        match self.config.faults.index {
+            Policy::Wrap => {
+                self.index = (addr as usize % self.memory.size()) as u32;
+                return Ok(());
+            }
+            // only the fault is ignored, I still points where the program
+            // asked, e.g. at the font
+            Policy::Ignore => {
+                self.index = addr;
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        if addr < self.config.memory_map.program_start as u32 {
             return Err(Error::InvalidIndexAddress(addr));
-        } else if addr >= 0x1000 {
+        } else if addr as usize >= self.memory.size() {
             return Err(Error::IndexOverflow(addr));
         }
 
@@ -26,16 +41,71 @@ impl Machine {
     }
 
     pub(super) fn op_add_index(&mut self, vx: u8) -> Result<()> {
-        let offset = self.registers[vx as usize] as u16;
-        let target = offset + self.index;
+        let offset = self.registers[vx as usize] as u32;
+        let target = self.index.wrapping_add(offset);
+
+        match self.config.faults.index {
+            Policy::Wrap => {
+                self.index = (target as usize % self.memory.size()) as u32;
+                return Ok(());
+            }
+            Policy::Ignore => {
+                self.index = target;
+                return Ok(());
+            }
+            _ => (),
+        }
 
-        if target < 0x200 {
+        if target < self.config.memory_map.program_start as u32 {
             return Err(Error::InvalidIndexAddress(target));
-        } else if target >= 0x1000 {
+        } else if target as usize >= self.memory.size() {
             return Err(Error::IndexOverflow(target));
         }
 
-        self.index += offset as u16;
+        self.index += offset;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::faults::{Faults, Policy};
+    use crate::program::Program;
+
+    #[test]
+    fn test_ignored_index_fault() {
+        // ROMs pointing I at the font without going through FX29
+        let program = Program(vec![
+            SetIndex(0x050),
+            SetImmediate { vx: 0, kk: 5 },
+            AddIndex(0),
+            Draw { vx: 1, vy: 1, n: 5 },
+        ]);
+
+        let mut config = Config::for_profile(Profile::Strict);
+        config.faults = Faults {
+            index: Policy::Ignore,
+            ..Faults::STRICT
+        };
+
+        let mut machine = Machine::with_config(config);
+        machine.load_program(program.into()).unwrap();
+        machine.step().unwrap();
+        assert_eq!(machine.get_index(), 0x050);
+        machine.step().unwrap();
+        machine.step().unwrap();
+        assert_eq!(machine.get_index(), 0x055);
+
+        // the glyph of 1 follows the one of 0
+        machine.step().unwrap();
+        let glyph = machine.get_memory().read(0x055).unwrap();
+        assert_ne!(glyph, 0);
+        for x in 0..8 {
+            let set = glyph & (0x80 >> x) != 0;
+            assert_eq!(machine.get_display().get_pixel(x, 0), set);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use rand::RngCore;
 
-use super::Machine;
+use super::{Machine, Status};
 use crate::error::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -8,12 +8,30 @@ type Result<T> = std::result::Result<T, Error>;
 // system instructions
 impl Machine {
     pub(super) fn op_clear(&mut self) -> Result<()> {
+        if let Some(megachip) = self
+            .megachip
+            .as_deref_mut()
+            .filter(|mega| mega.is_enabled())
+        {
+            megachip.present();
+            return Ok(());
+        }
+
         self.display.clear();
+        self.sync_framebuffer();
+
+        Ok(())
+    }
 
+    pub(super) fn op_syscall(&mut self, addr: u16) -> Result<()> {
+        if self.config.quircks.machine_code {
+            return self.run_machine_code(addr);
+        }
         Ok(())
     }
 
-    pub(super) fn op_syscall(&self, _: u16) -> Result<()> {
+    pub(super) fn op_stop(&mut self) -> Result<()> {
+        self.status = Status::Stopped;
         Ok(())
     }
 
@@ -1,9 +1,10 @@
+#[derive(Default, Clone)]
 pub struct Quircks {
     pub shift: bool,
-}
-
-impl Default for Quircks {
-    fn default() -> Self {
-        Self { shift: false }
-    }
+    // the keypad is scanned for one key at a time, programs only see the
+    // lowest key pressed
+    pub single_key: bool,
+    // 0NNN runs the 1802 machine code at NNN instead of being ignored,
+    // off in every profile
+    pub machine_code: bool,
 }
@@ -0,0 +1,117 @@
+use super::{Machine, Status};
+use crate::checksum::Crc32;
+use crate::display::Display;
+use crate::megachip::MegaChip;
+use crate::memory::Memory;
+use crate::vip::Vip;
+
+// Snapshot is a copy of the emulated state: memory, display, registers,
+// stack, timers and status, and the VIP system when it runs the program.
+// it can be restored into any machine, even one that never loaded the ROM.
+// config, RNG and the attached native program are left untouched.
+#[derive(Clone)]
+pub struct Snapshot {
+    memory: Memory,
+    display: Display,
+    megachip: Option<Box<MegaChip>>,
+    vip: Option<Box<Vip>>,
+
+    registers: [u8; 16],
+    stack: Vec<u16>,
+    pc: u16,
+    sp: u8,
+    dt: u8,
+    st: u8,
+    index: u32,
+    status: Status,
+}
+
+impl Machine {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            display: self.display.clone(),
+            megachip: self.megachip.clone(),
+            vip: self.vip.clone(),
+            registers: self.registers,
+            stack: self.stack.clone(),
+            pc: self.pc,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+            index: self.index,
+            status: self.status.clone(),
+        }
+    }
+
+    // CRC-32 of the emulated state, used to detect diverging runs
+    pub fn state_hash(&self) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(self.memory.as_slice());
+        crc.update(self.display.framebuffer());
+        if let Some(megachip) = &self.megachip {
+            crc.update(megachip.indices());
+        }
+        crc.update(&self.registers);
+        for addr in &self.stack {
+            crc.update(&addr.to_be_bytes());
+        }
+        crc.update(&self.pc.to_be_bytes());
+        crc.update(&[self.sp, self.dt, self.st]);
+        crc.update(&self.index.to_be_bytes());
+        crc.finish()
+    }
+
+    // restores the state and clears input and frame timing
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory.clone_from(&snapshot.memory);
+        self.display.copy_from(&snapshot.display);
+        self.megachip.clone_from(&snapshot.megachip);
+        self.vip.clone_from(&snapshot.vip);
+        self.registers = snapshot.registers;
+        self.stack.clone_from(&snapshot.stack);
+        self.pc = snapshot.pc;
+        self.sp = snapshot.sp;
+        self.dt = snapshot.dt;
+        self.st = snapshot.st;
+        self.index = snapshot.index;
+        self.status.clone_from(&snapshot.status);
+
+        self.keys.clear_all_keys();
+        self.keys2.clear_all_keys();
+        self.timer_accumulator = Default::default();
+        self.last_frame_time = Default::default();
+        self.frame_cycles = 0;
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.unwind();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruction::Instruction::*;
+    use crate::machine::config::{Config, Profile};
+    use crate::machine::{Machine, Status};
+    use crate::program::Program;
+
+    #[test]
+    fn test_restore_status() {
+        let program = Program(vec![SetImmediate { vx: 0, kk: 1 }, Stop]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_program(program.into()).unwrap();
+        let snapshot = machine.snapshot();
+
+        machine.step().unwrap();
+        assert!(!machine.step().unwrap());
+        assert!(matches!(machine.status(), Status::Stopped));
+
+        // the snapshot was taken while running
+        machine.restore(&snapshot);
+        assert!(matches!(machine.status(), Status::Running));
+        assert!(machine.step().unwrap());
+        assert_eq!(machine.get_registers()[0], 1);
+    }
+}
@@ -0,0 +1,298 @@
+// MegaChip state: a 256x192 screen of palette colors, the sprite settings
+// of the 03NN-09NN instructions and the digitised sample being played.
+// the machine creates it with the first MegaChip instruction, sprites only
+// go here after 0011 turned MegaChip mode on.
+//
+// sprites are width x height bytes of palette indices, index 0 is
+// transparent. they are blended into a back buffer which 00E0 presents
+// and clears, frontends show the presented frame.
+
+use crate::error::Error;
+use crate::render::Color;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+// how sprite pixels are combined with the pixels below them, 080N
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Blend {
+    // the palette color's own alpha
+    #[default]
+    Normal,
+    Alpha25,
+    Alpha50,
+    Alpha75,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Blend::Normal),
+            1 => Some(Blend::Alpha25),
+            2 => Some(Blend::Alpha50),
+            3 => Some(Blend::Alpha75),
+            4 => Some(Blend::Add),
+            5 => Some(Blend::Multiply),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, source: Color, target: Color) -> Color {
+        let mix = |alpha: u16| -> Color {
+            std::array::from_fn(|i| match i {
+                3 => 0xFF,
+                _ => ((source[i] as u16 * alpha + target[i] as u16 * (255 - alpha)) / 255) as u8,
+            })
+        };
+
+        match self {
+            Blend::Normal => mix(source[3] as u16),
+            Blend::Alpha25 => mix(64),
+            Blend::Alpha50 => mix(128),
+            Blend::Alpha75 => mix(191),
+            Blend::Add => std::array::from_fn(|i| source[i].saturating_add(target[i])),
+            Blend::Multiply => {
+                std::array::from_fn(|i| (source[i] as u16 * target[i] as u16 / 255) as u8)
+            }
+        }
+    }
+}
+
+// unsigned 8-bit samples at `rate` Hz, started by 060N
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub rate: u16,
+    pub data: Vec<u8>,
+    pub looping: bool,
+}
+
+impl Sample {
+    // 060N reads a 16-bit rate, a 24-bit length and a reserved byte
+    pub const HEADER_SIZE: usize = 6;
+}
+
+#[derive(Clone)]
+pub struct MegaChip {
+    enabled: bool,
+    palette: [Color; 256],
+
+    // back buffer, indices are kept for collision detection
+    indices: Vec<u8>,
+    pixels: Vec<Color>,
+    // the last frame presented by 00E0
+    frame: Vec<Color>,
+
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend: Blend,
+    collision_color: u8,
+
+    sample: Option<Sample>,
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            palette: [[0x00, 0x00, 0x00, 0xFF]; 256],
+            indices: vec![0; WIDTH * HEIGHT],
+            pixels: vec![[0x00, 0x00, 0x00, 0xFF]; WIDTH * HEIGHT],
+            frame: vec![[0x00, 0x00, 0x00, 0xFF]; WIDTH * HEIGHT],
+            sprite_width: 256,
+            sprite_height: 256,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision_color: 0,
+            sample: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // switching modes starts with a blank screen
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.clear();
+        self.frame.fill([0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    pub fn palette(&self) -> &[Color; 256] {
+        &self.palette
+    }
+
+    // ARGB bytes of palette entries 1, 2, ..., entry 0 is transparent
+    pub fn load_palette(&mut self, argb: &[u8]) {
+        let (colors, _) = argb.as_chunks::<4>();
+        for (entry, &[a, r, g, b]) in self.palette[1..].iter_mut().zip(colors) {
+            *entry = [r, g, b, a];
+        }
+    }
+
+    pub fn sprite_size(&self) -> (usize, usize) {
+        (self.sprite_width, self.sprite_height)
+    }
+
+    // 0 stands for 256
+    pub fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { width as usize };
+    }
+
+    pub fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 { 256 } else { height as usize };
+    }
+
+    // brightness of the presented frame, 0 fades it out to black
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub fn blend(&self) -> Blend {
+        self.blend
+    }
+
+    pub fn set_blend(&mut self, blend: Blend) {
+        self.blend = blend;
+    }
+
+    pub fn set_collision_color(&mut self, index: u8) {
+        self.collision_color = index;
+    }
+
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
+    }
+
+    pub fn set_sample(&mut self, sample: Option<Sample>) {
+        self.sample = sample;
+    }
+
+    // blends a sprite of the current size at (x, y), pixels beyond the
+    // screen are clipped. returns true if a pixel covered the collision
+    // color, which is never the case while it is the transparent 0.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let mut collision = false;
+
+        let rows = sprite.chunks(self.sprite_width).take(self.sprite_height);
+        for (y, row) in (y as usize..HEIGHT).zip(rows) {
+            for (x, &index) in (x as usize..WIDTH).zip(row) {
+                if index == 0 {
+                    continue;
+                }
+
+                let offset = y * WIDTH + x;
+                let below = self.indices[offset];
+                collision |= below != 0 && below == self.collision_color;
+                self.indices[offset] = index;
+                self.pixels[offset] = self
+                    .blend
+                    .apply(self.palette[index as usize], self.pixels[offset]);
+            }
+        }
+
+        collision
+    }
+
+    // shows the back buffer and starts the next frame on a blank one
+    pub fn present(&mut self) {
+        self.frame.copy_from_slice(&self.pixels);
+        self.clear();
+    }
+
+    // palette indices of the back buffer, row-major
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    // the presented frame, row-major
+    pub fn frame(&self) -> &[Color] {
+        &self.frame
+    }
+
+    // the presented frame as R, G, B, A bytes with the screen alpha applied
+    pub fn render_rgba(&self, out: &mut [u8]) -> Result<()> {
+        let expected = WIDTH * HEIGHT * 4;
+        if out.len() < expected {
+            return Err(Error::BufferTooSmall {
+                expected,
+                actual: out.len(),
+            });
+        }
+
+        let (out, _) = out.as_chunks_mut::<4>();
+        let fade = Blend::Normal;
+        for (pixel, &color) in out.iter_mut().zip(&self.frame) {
+            let [r, g, b, _] = color;
+            *pixel = fade.apply([r, g, b, self.alpha], [0x00, 0x00, 0x00, 0xFF]);
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.indices.fill(0);
+        self.pixels.fill([0x00, 0x00, 0x00, 0xFF]);
+    }
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Blend, HEIGHT, MegaChip, WIDTH};
+
+    #[test]
+    fn test_draw_sprite() {
+        let mut mega = MegaChip::new();
+        mega.set_enabled(true);
+        mega.load_palette(&[0xFF, 0xFF, 0x00, 0x00, 0x80, 0x00, 0x00, 0xFF]);
+        mega.set_sprite_width(2);
+        mega.set_sprite_height(2);
+        mega.set_collision_color(1);
+
+        // index 0 is transparent
+        assert!(!mega.draw_sprite(254, 190, &[1, 0, 1, 1]));
+        assert_eq!(mega.indices()[190 * WIDTH + 254], 1);
+        assert_eq!(mega.indices()[190 * WIDTH + 255], 0);
+
+        // clipped at the right edge, collides with the red pixel
+        mega.set_blend(Blend::Add);
+        assert!(mega.draw_sprite(255, 191, &[2, 2, 2, 2]));
+        assert_eq!(mega.indices()[191 * WIDTH + 255], 2);
+
+        mega.present();
+        assert_eq!(mega.frame()[190 * WIDTH + 254], [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(mega.frame()[191 * WIDTH + 255], [0xFF, 0x00, 0xFF, 0xFF]);
+        assert!(mega.indices().iter().all(|&index| index == 0));
+
+        mega.set_alpha(0x80);
+        let mut out = vec![0; WIDTH * HEIGHT * 4];
+        mega.render_rgba(&mut out).unwrap();
+        let offset = (190 * WIDTH + 254) * 4;
+        assert_eq!(out[offset..offset + 4], [0x80, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_blend() {
+        let (source, target) = ([200, 100, 0, 0xFF], [100, 200, 40, 0xFF]);
+        assert_eq!(Blend::Normal.apply(source, target), [200, 100, 0, 0xFF]);
+        assert_eq!(Blend::Alpha50.apply(source, target), [150, 149, 19, 0xFF]);
+        assert_eq!(Blend::Add.apply(source, target), [255, 255, 40, 0xFF]);
+        assert_eq!(Blend::Multiply.apply(source, target), [78, 78, 0, 0xFF]);
+        assert_eq!(Blend::from_code(6), None);
+    }
+}
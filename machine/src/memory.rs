@@ -1,56 +1,159 @@
 use super::error::Error;
+use crate::font::Font;
 type Result<T> = std::result::Result<T, Error>;
 
-const FONTS: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+// layout of the address space. the stack and the framebuffer normally
+// live outside of memory, mapping them makes them visible to the program
+// the way they were on the COSMAC VIP.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    // bytes of addressable memory, up to the 16 MiB reachable with the
+    // 24-bit MegaChip index. code only runs from the first 64 KiB
+    pub size: usize,
+    pub program_start: u16,
+    pub font_base: u16,
+    // number of return addresses the stack holds
+    pub stack_depth: u8,
+    // start of the memory area holding the stack. return addresses are
+    // big-endian words growing downwards from the end of the area
+    pub stack: Option<u16>,
+    // address of the 1-bit framebuffer, see Display::framebuffer
+    pub framebuffer: Option<u16>,
+}
+
+impl MemoryMap {
+    pub const COSMAC_VIP: MemoryMap = MemoryMap {
+        size: 0x1000,
+        program_start: 0x200,
+        font_base: 0x050,
+        stack_depth: 24,
+        stack: Some(0xEA0),
+        framebuffer: Some(0xF00),
+    };
+
+    // 16 MB reachable through 01NN NNNN
+    pub const MEGACHIP: MemoryMap = MemoryMap {
+        size: 0x1000000,
+        program_start: 0x200,
+        font_base: 0x050,
+        stack_depth: 16,
+        stack: None,
+        framebuffer: None,
+    };
+
+    // the CHIP-8X interpreter is larger and programs start after it
+    pub const CHIP_8X: MemoryMap = MemoryMap {
+        program_start: 0x300,
+        ..MemoryMap::COSMAC_VIP
+    };
+
+    // ETI-660 programs load at 0x600, the display of 48 rows does not
+    // fit the VIP's page at 0xF00 and is kept out of memory
+    pub const ETI_660: MemoryMap = MemoryMap {
+        size: 0x1000,
+        program_start: 0x600,
+        font_base: 0x050,
+        stack_depth: 16,
+        stack: None,
+        framebuffer: None,
+    };
+
+    // the hires interpreter's display takes two pages, the stack moves
+    // below them
+    pub const HIRES: MemoryMap = MemoryMap {
+        stack: Some(0xDA0),
+        framebuffer: Some(0xE00),
+        ..MemoryMap::COSMAC_VIP
+    };
+
+    // a mapped stack has to hold at least one entry and fit in memory
+    pub fn validate(&self) -> Result<()> {
+        let Some(base) = self.stack else {
+            return Ok(());
+        };
+
+        if self.stack_depth == 0 {
+            return Err(Error::InvalidMemoryMap(
+                "mapped stack without entries".to_string(),
+            ));
+        }
+
+        let end = base as usize + 2 * self.stack_depth as usize;
+        if end > self.size.min(0x10000) {
+            return Err(Error::InvalidMemoryMap(format!(
+                "stack at {:#05X} ends beyond memory",
+                base
+            )));
+        }
+
+        Ok(())
+    }
 
+    // address of the n-th return address when the stack is mapped,
+    // None for entries a map that fails validate doesn't have
+    pub fn stack_entry(&self, n: u8) -> Option<u16> {
+        let base = self.stack?;
+        let slot = self.stack_depth.checked_sub(n)?.checked_sub(1)?;
+        base.checked_add(2 * slot as u16)
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self {
+            size: 0x1000,
+            program_start: 0x200,
+            font_base: 0x050,
+            stack_depth: 16,
+            stack: None,
+            framebuffer: None,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Memory {
-    data: [u8; 4096],
+    data: Vec<u8>,
 }
 
 impl Memory {
     pub fn new() -> Self {
-        let mut memory = Self { data: [0; 4096] };
-        memory.load_fonts();
+        Self::with_map(&MemoryMap::default(), &Font::default())
+    }
+
+    pub fn with_map(map: &MemoryMap, font: &Font) -> Self {
+        let mut memory = Self {
+            data: vec![0; map.size],
+        };
+        memory.load_font(map.font_base, font);
 
-        return memory;
+        memory
     }
 
-    pub fn read(&self, addr: u16) -> Result<u8> {
-        if addr >= 0x1000 {
-            Err(Error::MemoryOutOfBound)
-        } else {
-            Ok(self.data[addr as usize])
-        }
+    pub fn size(&self) -> usize {
+        self.data.len()
     }
 
-    pub fn write(&mut self, addr: u16, value: u8) -> Result<()> {
-        if addr >= 0x1000 {
-            Err(Error::MemoryOutOfBound)
-        } else {
-            self.data[addr as usize] = value;
-            Ok(())
-        }
+    pub fn read(&self, addr: u32) -> Result<u8> {
+        self.data
+            .get(addr as usize)
+            .copied()
+            .ok_or(Error::MemoryOutOfBound(addr))
+    }
+
+    pub fn write(&mut self, addr: u32, value: u8) -> Result<()> {
+        let byte = self
+            .data
+            .get_mut(addr as usize)
+            .ok_or(Error::MemoryOutOfBound(addr))?;
+
+        *byte = value;
+        Ok(())
     }
-    pub fn write_word(&mut self, addr: u16, value: u16) -> Result<()> {
-        if addr >= 0x1000 - 1 {
-            return Err(Error::MemoryOutOfBound);
+
+    pub fn write_word(&mut self, addr: u32, value: u16) -> Result<()> {
+        if addr as usize + 1 >= self.data.len() {
+            return Err(Error::MemoryOutOfBound(addr));
         }
 
         let high = (value >> 8) as u8;
@@ -61,21 +164,27 @@ impl Memory {
         Ok(())
     }
 
-    pub fn read_range(&self, start: u16, length: u16) -> Vec<u8> {
-        let start_idx = start as usize;
-        let end = start as u32 + length as u32;
-
-        if end > 0x1000 {
-            Vec::new()
-        } else {
-            let end_idx = (start + length) as usize;
-            self.data[start_idx..end_idx].to_vec()
+    pub fn read_range(&self, start: u32, length: usize) -> Vec<u8> {
+        match self.slice(start, length) {
+            Some(data) => data.to_vec(),
+            None => Vec::new(),
         }
     }
 
-    pub fn read_word(&self, addr: u16) -> Result<u16> {
-        if addr >= 0x1000 - 1 {
-            return Err(Error::MemoryOutOfBound);
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    // borrows a range of memory without copying it,
+    // returns None if the range goes beyond the end of memory
+    pub fn slice(&self, start: u32, length: usize) -> Option<&[u8]> {
+        let end = start as usize + length;
+        self.data.get(start as usize..end)
+    }
+
+    pub fn read_word(&self, addr: u32) -> Result<u16> {
+        if addr as usize + 1 >= self.data.len() {
+            return Err(Error::MemoryOutOfBound(addr));
         }
 
         let high = self.data[addr as usize] as u16;
@@ -85,11 +194,95 @@ impl Memory {
         Ok(result)
     }
 
-    fn load_fonts(&mut self) {
-        let addr = 0x050;
-        for (i, byte) in FONTS.iter().copied().enumerate() {
-            let target = addr + i;
-            self.data[target as usize] = byte;
+    fn load_font(&mut self, addr: u16, font: &Font) {
+        for (i, byte) in font.bytes().enumerate() {
+            if let Some(target) = self.data.get_mut(addr as usize + i) {
+                *target = byte;
+            }
         }
     }
 }
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryMap;
+    use crate::error::Error;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::program::Program;
+
+    #[test]
+    fn test_stack_entry() {
+        let map = MemoryMap::COSMAC_VIP;
+        assert_eq!(map.stack_entry(0), Some(0xECE));
+        assert_eq!(map.stack_entry(23), Some(0xEA0));
+        assert_eq!(MemoryMap::default().stack_entry(0), None);
+    }
+
+    #[test]
+    fn test_invalid_stack() {
+        let empty = MemoryMap {
+            stack_depth: 0,
+            ..MemoryMap::COSMAC_VIP
+        };
+        assert!(matches!(empty.validate(), Err(Error::InvalidMemoryMap(_))));
+        assert_eq!(empty.stack_entry(0), None);
+
+        let beyond = MemoryMap {
+            stack: Some(0xFF0),
+            ..MemoryMap::COSMAC_VIP
+        };
+        assert!(matches!(beyond.validate(), Err(Error::InvalidMemoryMap(_))));
+        assert!(MemoryMap::COSMAC_VIP.validate().is_ok());
+
+        let mut config = Config::for_profile(Profile::CosmacVip);
+        config.memory_map = empty;
+        let mut machine = Machine::with_config(config);
+        assert!(matches!(
+            machine.load_program(vec![0x00EE]),
+            Err(Error::InvalidMemoryMap(_))
+        ));
+    }
+
+    #[test]
+    fn test_mapped_areas() {
+        let program = Program(vec![
+            Call(0x204),                      // 0x200
+            Jump(0x202),                      // 0x202
+            SetIndex(0xF00),                  // 0x204
+            SetImmediate { vx: 0, kk: 0xFF }, // 0x206
+            StoreRegisters(0),                // 0x208
+            LoadFont(2),                      // 0x20A
+            SetImmediate { vx: 1, kk: 8 },    // 0x20C
+            Draw { vx: 1, vy: 2, n: 5 },      // 0x20E
+            Return,                           // 0x210
+        ]);
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::CosmacVip));
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..8 {
+            machine.step().unwrap();
+        }
+
+        let memory = machine.get_memory();
+        assert_eq!(memory.read_word(0xECE).unwrap(), 0x202);
+
+        // written through memory, drawn on the display
+        assert!(machine.get_display().get_pixel(0, 0));
+        assert!(machine.get_display().get_pixel(7, 0));
+
+        // drawn on the display, visible in memory
+        assert_eq!(memory.read(0xF01).unwrap(), 0xF0);
+        assert_eq!(memory.read(0xF09).unwrap(), 0x90);
+
+        machine.step().unwrap();
+        assert_eq!(machine.get_pc(), 0x202);
+    }
+}
@@ -0,0 +1,670 @@
+// Input movies: everything needed to replay a session exactly. a movie
+// stores the ROM hash, config and RNG seed, the keyboard state of every
+// frame and optionally state hashes every N frames to catch desyncs.
+// CHIP-8X input, the second keypad and the input port, is stored along the
+// keys when it is used.
+//
+// Recorder and Player wrap a frontend platform. both drive the machine with
+// a virtual clock advancing exactly one timer period per frame, so the same
+// input always executes the same instructions.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use crate::checksum::crc32;
+use crate::display::{Display, Rect};
+use crate::error::Error;
+use crate::font::Font;
+use crate::keyboard::Keyboard;
+use crate::machine::Machine;
+use crate::machine::config::{Config, Profile};
+use crate::machine::faults::Policy;
+use crate::megachip::{MegaChip, Sample};
+use crate::platform::{ExecutionMode, Platform};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const HEADER: &str = "octochip-movie 1";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub keys: u16,
+    pub second_keys: u16,
+    pub input_port: u8,
+    // hash of the machine state at the end of the frame
+    pub state_hash: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct Movie {
+    pub rom_hash: u32,
+    pub config: Config,
+    pub seed: u64,
+    // frames between state hashes, 0 disables them
+    pub hash_interval: u32,
+    pub frames: Vec<Frame>,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], config: Config, seed: u64) -> Self {
+        Self {
+            rom_hash: crc32(rom),
+            config,
+            seed,
+            hash_interval: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn with_hash_interval(mut self, frames: u32) -> Self {
+        self.hash_interval = frames;
+        self
+    }
+
+    // creates a machine in the state the movie starts from,
+    // both recording and playback have to start with it
+    pub fn machine(&self, rom: &[u8]) -> Result<Machine> {
+        let hash = crc32(rom);
+        if hash != self.rom_hash {
+            return Err(Error::RomHashMismatch(hash));
+        }
+
+        let mut machine = Machine::with_config(self.config.clone());
+        machine.reseed(self.seed);
+        machine.load_rom(rom)?;
+        Ok(machine)
+    }
+
+    fn frame_period(&self) -> Duration {
+        Duration::from_secs(1) / self.config.timer_frequency as u32
+    }
+
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+
+        output.push_str(&format!("{}\n", HEADER));
+        output.push_str(&format!("rom_crc32 {:08X}\n", self.rom_hash));
+        output.push_str(&format!("seed {}\n", self.seed));
+        output.push_str(&format!("profile {}\n", self.config.profile.name()));
+        output.push_str(&format!("cpu_frequency {}\n", self.config.cpu_frequency));
+        output.push_str(&format!(
+            "timer_frequency {}\n",
+            self.config.timer_frequency
+        ));
+        output.push_str(&format!(
+            "quirk_shift {}\n",
+            self.config.quircks.shift as u8
+        ));
+        output.push_str(&format!(
+            "quirk_single_key {}\n",
+            self.config.quircks.single_key as u8
+        ));
+        output.push_str(&format!(
+            "quirk_machine_code {}\n",
+            self.config.quircks.machine_code as u8
+        ));
+        let faults = &self.config.faults;
+        for (name, policy) in [
+            ("index", faults.index),
+            ("stack", faults.stack),
+            ("instruction", faults.instruction),
+            ("memory", faults.memory),
+            ("program_counter", faults.program_counter),
+        ] {
+            output.push_str(&format!("fault_{} {}\n", name, policy.name()));
+        }
+        let map = &self.config.memory_map;
+        let area = |addr: Option<u16>| match addr {
+            Some(addr) => format!("{:04X}", addr),
+            None => "none".to_string(),
+        };
+        output.push_str(&format!("memory_size {}\n", map.size));
+        output.push_str(&format!("program_start {:04X}\n", map.program_start));
+        output.push_str(&format!("font_base {:04X}\n", map.font_base));
+        output.push_str(&format!("stack_depth {}\n", map.stack_depth));
+        output.push_str(&format!("stack {}\n", area(map.stack)));
+        output.push_str(&format!("framebuffer {}\n", area(map.framebuffer)));
+        let geometry = &self.config.geometry;
+        output.push_str(&format!("display_width {}\n", geometry.width));
+        output.push_str(&format!("display_height {}\n", geometry.height));
+        let bytes = |data: &[u8]| match data.is_empty() {
+            true => "none".to_string(),
+            false => data.iter().map(|byte| format!("{:02X}", byte)).collect(),
+        };
+        let font = &self.config.font;
+        output.push_str(&format!("font_small {}\n", bytes(font.small())));
+        output.push_str(&format!("font_big {}\n", bytes(font.big())));
+        output.push_str(&format!("hash_interval {}\n", self.hash_interval));
+        output.push_str("frames\n");
+
+        for frame in self.frames.iter() {
+            let mut input = format!("{:04X}", frame.keys);
+            if frame.second_keys != 0 || frame.input_port != 0 {
+                input += &format!(":{:04X}:{:02X}", frame.second_keys, frame.input_port);
+            }
+
+            match frame.state_hash {
+                Some(hash) => output.push_str(&format!("{} {:08X}\n", input, hash)),
+                None => output.push_str(&format!("{}\n", input)),
+            }
+        }
+
+        output
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |line: &str| Error::InvalidMovie(line.to_string());
+        let policy = |value: &str| Policy::parse(value).ok_or_else(|| invalid(value));
+        let bytes = |value: &str| -> Result<Vec<u8>> {
+            if value == "none" {
+                return Ok(Vec::new());
+            }
+
+            (0..value.len())
+                .step_by(2)
+                .map(|i| {
+                    let byte = value.get(i..i + 2).ok_or_else(|| invalid(value))?;
+                    u8::from_str_radix(byte, 16).map_err(|_| invalid(value))
+                })
+                .collect()
+        };
+        let area = |value: &str| match value {
+            "none" => Ok(None),
+            _ => hex(value).map(Some),
+        };
+
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("missing header"));
+        }
+
+        let mut movie = Self {
+            rom_hash: 0,
+            config: Config::default(),
+            seed: 0,
+            hash_interval: 0,
+            frames: Vec::new(),
+        };
+
+        for line in lines.by_ref() {
+            if line == "frames" {
+                break;
+            }
+
+            let (key, value) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            match key {
+                "rom_crc32" => movie.rom_hash = hex(value)?,
+                "seed" => movie.seed = dec(value)?,
+                "profile" => {
+                    let profile = Profile::parse(value).ok_or_else(|| invalid(value))?;
+                    movie.config = Config {
+                        cpu_frequency: movie.config.cpu_frequency,
+                        timer_frequency: movie.config.timer_frequency,
+                        ..Config::for_profile(profile)
+                    };
+                }
+                "cpu_frequency" => movie.config.cpu_frequency = dec(value)?,
+                "timer_frequency" => movie.config.timer_frequency = dec(value)?,
+                "quirk_shift" => movie.config.quircks.shift = dec::<u64>(value)? != 0,
+                "quirk_single_key" => movie.config.quircks.single_key = dec::<u64>(value)? != 0,
+                "quirk_machine_code" => movie.config.quircks.machine_code = dec::<u64>(value)? != 0,
+                "fault_index" => movie.config.faults.index = policy(value)?,
+                "fault_stack" => movie.config.faults.stack = policy(value)?,
+                "fault_instruction" => movie.config.faults.instruction = policy(value)?,
+                "fault_memory" => movie.config.faults.memory = policy(value)?,
+                "fault_program_counter" => movie.config.faults.program_counter = policy(value)?,
+                "memory_size" => movie.config.memory_map.size = dec(value)?,
+                "program_start" => movie.config.memory_map.program_start = hex(value)?,
+                "font_base" => movie.config.memory_map.font_base = hex(value)?,
+                "stack_depth" => movie.config.memory_map.stack_depth = dec(value)?,
+                "stack" => movie.config.memory_map.stack = area(value)?,
+                "framebuffer" => movie.config.memory_map.framebuffer = area(value)?,
+                "display_width" => match dec(value)? {
+                    // the display only supports whole bytes per row
+                    width @ 8..=128u8 if width.is_multiple_of(8) => {
+                        movie.config.geometry.width = width
+                    }
+                    _ => return Err(invalid(value)),
+                },
+                "display_height" => movie.config.geometry.height = dec(value)?,
+                "font_small" => {
+                    let big = movie.config.font.big().to_vec();
+                    movie.config.font = Font::from_bytes(&bytes(value)?, &big)?;
+                }
+                "font_big" => {
+                    let small = movie.config.font.small().to_vec();
+                    movie.config.font = Font::from_bytes(&small, &bytes(value)?)?;
+                }
+                "hash_interval" => movie.hash_interval = dec(value)?,
+                _ => return Err(invalid(line)),
+            }
+        }
+
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let mut input = fields.next().ok_or_else(|| invalid(line))?.split(':');
+            let keys = input.next().map(hex).transpose()?.unwrap_or(0);
+            let second_keys = input.next().map(hex).transpose()?.unwrap_or(0);
+            let input_port = input.next().map(hex).transpose()?.unwrap_or(0);
+            let state_hash = fields.next().map(hex).transpose()?;
+
+            movie.frames.push(Frame {
+                keys,
+                second_keys,
+                input_port,
+                state_hash,
+            });
+        }
+
+        Ok(movie)
+    }
+}
+
+// parses a value of a movie line, out of range values are rejected
+fn number<T: TryFrom<u64>>(value: &str, radix: u32) -> Result<T> {
+    u64::from_str_radix(value, radix)
+        .ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| Error::InvalidMovie(value.to_string()))
+}
+
+fn hex<T: TryFrom<u64>>(value: &str) -> Result<T> {
+    number(value, 16)
+}
+
+fn dec<T: TryFrom<u64>>(value: &str) -> Result<T> {
+    number(value, 10)
+}
+
+// Recorder passes everything through to the wrapped platform and records
+// the keys of every running frame. single stepping can't be replayed from
+// a movie, so Step mode is reported to the machine as Paused.
+pub struct Recorder<P> {
+    platform: P,
+    movie: Movie,
+
+    keys: Cell<Keyboard>,
+    second_keys: Cell<Keyboard>,
+    input_port: Cell<u8>,
+    running: Cell<bool>,
+}
+
+impl<P: Platform> Recorder<P> {
+    // the machine has to come from movie.machine
+    pub fn new(platform: P, movie: Movie) -> Self {
+        Self {
+            platform,
+            movie,
+            keys: Cell::new(Keyboard::new()),
+            second_keys: Cell::new(Keyboard::new()),
+            input_port: Cell::new(0),
+            running: Cell::new(false),
+        }
+    }
+
+    pub fn platform_mut(&mut self) -> &mut P {
+        &mut self.platform
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn into_movie(self) -> Movie {
+        self.movie
+    }
+}
+
+impl<P: Platform> Platform for Recorder<P> {
+    type Error = P::Error;
+
+    fn get_keys(&self) -> Keyboard {
+        let keys = self.platform.get_keys();
+        self.keys.set(keys);
+        keys
+    }
+
+    fn get_second_keys(&self) -> Keyboard {
+        let keys = self.platform.get_second_keys();
+        self.second_keys.set(keys);
+        keys
+    }
+
+    fn get_input_port(&self) -> u8 {
+        let value = self.platform.get_input_port();
+        self.input_port.set(value);
+        value
+    }
+
+    fn write_port(&mut self, value: u8) -> std::result::Result<(), Self::Error> {
+        self.platform.write_port(value)
+    }
+
+    fn draw_display(&mut self, display: &Display) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_display(display)
+    }
+
+    fn only_changed_frames(&self) -> bool {
+        self.platform.only_changed_frames()
+    }
+
+    fn draw_changes(
+        &mut self,
+        display: &Display,
+        dirty: &[Rect],
+    ) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_changes(display, dirty)
+    }
+
+    fn draw_megachip(&mut self, megachip: &MegaChip) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_megachip(megachip)
+    }
+
+    fn play_sound(&mut self, enabled: bool) -> std::result::Result<(), Self::Error> {
+        self.platform.play_sound(enabled)
+    }
+
+    fn play_sample(&mut self, sample: Option<&Sample>) -> std::result::Result<(), Self::Error> {
+        self.platform.play_sample(sample)
+    }
+
+    fn get_time(&self) -> Duration {
+        self.movie.frame_period() * (self.movie.frames.len() as u32 + 1)
+    }
+
+    fn get_execution_mode(&self) -> ExecutionMode {
+        let running = matches!(self.platform.get_execution_mode(), ExecutionMode::Running);
+        self.running.set(running);
+
+        if running {
+            ExecutionMode::Running
+        } else {
+            ExecutionMode::Paused
+        }
+    }
+
+    fn end_frame(&mut self, machine: &Machine) -> std::result::Result<(), Self::Error> {
+        if self.running.get() {
+            let number = self.movie.frames.len() as u32 + 1;
+            let interval = self.movie.hash_interval;
+            let state_hash =
+                (interval != 0 && number.is_multiple_of(interval)).then(|| machine.state_hash());
+
+            self.movie.frames.push(Frame {
+                keys: self.keys.get().get_keys(),
+                second_keys: self.second_keys.get().get_keys(),
+                input_port: self.input_port.get(),
+                state_hash,
+            });
+        }
+
+        self.platform.end_frame(machine)
+    }
+}
+
+// Player feeds recorded input back to the machine and pauses it once the
+// movie is over. the wrapped platform only gets the output. if a recorded
+// state hash doesn't match, run_frame fails with Error::MovieDesync.
+pub struct Player<P> {
+    platform: P,
+    movie: Movie,
+    frame: usize,
+}
+
+impl<P: Platform> Player<P> {
+    // the machine has to come from movie.machine
+    pub fn new(platform: P, movie: Movie) -> Self {
+        Self {
+            platform,
+            movie,
+            frame: 0,
+        }
+    }
+
+    pub fn platform_mut(&mut self) -> &mut P {
+        &mut self.platform
+    }
+
+    // number of frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+}
+
+impl<P: Platform> Platform for Player<P> {
+    type Error = P::Error;
+
+    fn get_keys(&self) -> Keyboard {
+        self.movie
+            .frames
+            .get(self.frame)
+            .map_or(Keyboard::new(), |frame| Keyboard::from(frame.keys))
+    }
+
+    fn get_second_keys(&self) -> Keyboard {
+        self.movie
+            .frames
+            .get(self.frame)
+            .map_or(Keyboard::new(), |frame| Keyboard::from(frame.second_keys))
+    }
+
+    fn get_input_port(&self) -> u8 {
+        self.movie
+            .frames
+            .get(self.frame)
+            .map_or(0, |frame| frame.input_port)
+    }
+
+    fn write_port(&mut self, value: u8) -> std::result::Result<(), Self::Error> {
+        self.platform.write_port(value)
+    }
+
+    fn draw_display(&mut self, display: &Display) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_display(display)
+    }
+
+    fn only_changed_frames(&self) -> bool {
+        self.platform.only_changed_frames()
+    }
+
+    fn draw_changes(
+        &mut self,
+        display: &Display,
+        dirty: &[Rect],
+    ) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_changes(display, dirty)
+    }
+
+    fn draw_megachip(&mut self, megachip: &MegaChip) -> std::result::Result<(), Self::Error> {
+        self.platform.draw_megachip(megachip)
+    }
+
+    fn play_sound(&mut self, enabled: bool) -> std::result::Result<(), Self::Error> {
+        self.platform.play_sound(enabled)
+    }
+
+    fn play_sample(&mut self, sample: Option<&Sample>) -> std::result::Result<(), Self::Error> {
+        self.platform.play_sample(sample)
+    }
+
+    fn get_time(&self) -> Duration {
+        self.movie.frame_period() * (self.frame as u32 + 1)
+    }
+
+    fn get_execution_mode(&self) -> ExecutionMode {
+        if self.is_finished() {
+            ExecutionMode::Paused
+        } else {
+            ExecutionMode::Running
+        }
+    }
+
+    fn end_frame(&mut self, machine: &Machine) -> std::result::Result<(), Self::Error> {
+        if let Some(frame) = self.movie.frames.get(self.frame) {
+            if let Some(expected) = frame.state_hash {
+                let actual = machine.state_hash();
+                if actual != expected {
+                    return Err(Error::MovieDesync {
+                        frame: self.frame,
+                        expected,
+                        actual,
+                    }
+                    .into());
+                }
+            }
+
+            self.frame += 1;
+        }
+
+        self.platform.end_frame(machine)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::{Movie, Player, Recorder};
+    use crate::display::Display;
+    use crate::error::Error;
+    use crate::instruction::Instruction::*;
+    use crate::keyboard::Keyboard;
+    use crate::machine::config::{Config, Profile};
+    use crate::platform::{ExecutionMode, Platform};
+    use crate::program::Program;
+
+    // presses key 1 on odd frames, counts the frames it was asked for
+    struct Frontend {
+        frame: Cell<u16>,
+    }
+
+    impl Platform for Frontend {
+        type Error = Error;
+
+        fn get_keys(&self) -> Keyboard {
+            let frame = self.frame.get();
+            self.frame.set(frame + 1);
+            Keyboard::from((frame % 2) << 1)
+        }
+
+        fn get_second_keys(&self) -> Keyboard {
+            Keyboard::from(self.frame.get() % 3)
+        }
+
+        fn draw_display(&mut self, _: &Display) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn play_sound(&mut self, _: bool) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_time(&self) -> Duration {
+            Duration::from_secs(self.frame.get() as u64)
+        }
+
+        fn get_execution_mode(&self) -> ExecutionMode {
+            ExecutionMode::Running
+        }
+    }
+
+    fn rom() -> Vec<u8> {
+        // random values stored while key 1 is held, counted in V2
+        let program = Program(vec![
+            SetImmediate { vx: 1, kk: 1 },
+            SetIndex(0x300),
+            SkipIfNotKey(1),
+            AddImmediate { vx: 2, kk: 1 },
+            Rnd { vx: 0, kk: 0xFF },
+            StoreRegisters(2),
+            Jump(0x204),
+        ]);
+
+        let words: Vec<u16> = program.into();
+        words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let rom = rom();
+        let movie = Movie::new(&rom, Config::default(), 7).with_hash_interval(4);
+
+        let mut machine = movie.machine(&rom).unwrap();
+        let frontend = Frontend {
+            frame: Cell::new(0),
+        };
+        let mut recorder = Recorder::new(frontend, movie);
+        for _ in 0..10 {
+            machine.run_frame(&mut recorder).unwrap();
+        }
+        let recorded_hash = machine.state_hash();
+
+        let movie = Movie::parse(&recorder.into_movie().dump()).unwrap();
+        assert_eq!(movie.frames.len(), 10);
+        assert_eq!(movie.frames[1].keys, 0b10);
+        assert_eq!(movie.frames[1].second_keys, 2);
+        assert_eq!(movie.frames[2].second_keys, 0);
+        assert!(movie.frames[3].state_hash.is_some());
+        assert!(movie.frames[4].state_hash.is_none());
+        assert_eq!(movie.config.profile, Profile::Modern);
+        assert_eq!(movie.config.faults, Profile::Modern.faults());
+        assert_eq!(movie.config.memory_map, Profile::Modern.memory_map());
+        assert_eq!(movie.config.font, Profile::Modern.font());
+
+        let mut machine = movie.machine(&rom).unwrap();
+        let frontend = Frontend {
+            frame: Cell::new(0),
+        };
+        let mut player = Player::new(frontend, movie.clone());
+        while !player.is_finished() {
+            machine.run_frame(&mut player).unwrap();
+        }
+        assert_eq!(machine.state_hash(), recorded_hash);
+
+        let mut movie = movie;
+        movie.frames[7].state_hash = Some(0);
+        let mut machine = movie.machine(&rom).unwrap();
+        let frontend = Frontend {
+            frame: Cell::new(0),
+        };
+        let mut player = Player::new(frontend, movie);
+        let result = (0..10).try_for_each(|_| machine.run_frame(&mut player).map(|_| ()));
+        assert!(matches!(result, Err(Error::MovieDesync { frame: 7, .. })));
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let movie = Movie::new(&rom(), Config::default(), 7);
+        assert!(matches!(
+            movie.machine(&[0x12, 0x00]),
+            Err(Error::RomHashMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_values() {
+        let dump = Movie::new(&rom(), Config::default(), 7).dump();
+        assert!(Movie::parse(&dump).is_ok());
+
+        let mut texts: Vec<String> = [("cpu_frequency", "65536"), ("stack_depth", "256")]
+            .iter()
+            .map(|(key, value)| {
+                let line = dump.lines().find(|line| line.starts_with(key)).unwrap();
+                dump.replacen(line, &format!("{} {}", key, value), 1)
+            })
+            .collect();
+
+        // keys and input port of a frame
+        texts.push(format!("{}10000\n", dump));
+        texts.push(format!("{}0000:0000:100\n", dump));
+
+        for text in texts {
+            assert!(matches!(Movie::parse(&text), Err(Error::InvalidMovie(_))));
+        }
+    }
+}
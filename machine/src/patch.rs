@@ -0,0 +1,21 @@
+// ROM patches in the IPS and BPS formats, applied to ROM bytes before
+// they are loaded with Machine::load_rom. both formats can also be
+// created from two versions of a ROM.
+
+use crate::error::Error;
+
+pub mod bps;
+pub mod ips;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// applies an IPS or BPS patch, the format is detected from its header
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    if patch.starts_with(ips::MAGIC) {
+        ips::apply(rom, patch)
+    } else if patch.starts_with(bps::MAGIC) {
+        bps::apply(rom, patch)
+    } else {
+        Err(Error::InvalidPatch("unknown format".to_string()))
+    }
+}
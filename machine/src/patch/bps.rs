@@ -0,0 +1,355 @@
+// BPS: a stream of copy actions building the target from the source,
+// the patch itself and both ROMs are protected with CRC-32 checksums.
+
+use super::Result;
+use crate::checksum::crc32;
+use crate::error::Error;
+
+pub const MAGIC: &[u8] = b"BPS1";
+const FOOTER: usize = 12;
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let invalid = |reason: &str| Error::InvalidPatch(reason.to_string());
+
+    if !patch.starts_with(MAGIC) || patch.len() < MAGIC.len() + FOOTER {
+        return Err(invalid("missing BPS header"));
+    }
+
+    let footer = patch.len() - FOOTER;
+    let checksum = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+    verify("patch", checksum(footer + 8), crc32(&patch[..footer + 8]))?;
+    verify("source", checksum(footer), crc32(source))?;
+
+    let mut reader = Reader {
+        data: &patch[..footer],
+        pos: MAGIC.len(),
+    };
+
+    let source_size = reader.number()? as usize;
+    let target_size = reader.number()? as usize;
+    let metadata_size = reader.number()? as usize;
+    reader.bytes(metadata_size)?;
+
+    if source_size != source.len() {
+        return Err(invalid("source size mismatch"));
+    }
+
+    // the declared size comes from the patch, so it only bounds the output
+    // and isn't trusted for the allocation
+    let mut target = Vec::with_capacity(target_size.min(source.len() + patch.len()));
+    let mut source_offset: i64 = 0;
+    let mut target_offset: i64 = 0;
+
+    while reader.pos < reader.data.len() {
+        let action = reader.number()?;
+        let length = (action >> 2) as usize + 1;
+
+        if length > target_size - target.len() {
+            return Err(invalid("target larger than declared"));
+        }
+
+        match action & 3 {
+            SOURCE_READ => {
+                let data = source
+                    .get(target.len()..)
+                    .and_then(|data| data.get(..length))
+                    .ok_or_else(|| invalid("source read out of bounds"))?;
+                target.extend_from_slice(data);
+            }
+            TARGET_READ => target.extend_from_slice(reader.bytes(length)?),
+            SOURCE_COPY => {
+                source_offset = offset(source_offset, reader.signed()?)?;
+                let data = usize::try_from(source_offset)
+                    .ok()
+                    .and_then(|start| source.get(start..))
+                    .and_then(|data| data.get(..length))
+                    .ok_or_else(|| invalid("source copy out of bounds"))?;
+                target.extend_from_slice(data);
+                source_offset += length as i64;
+            }
+            TARGET_COPY => {
+                target_offset = offset(target_offset, reader.signed()?)?;
+                // copies byte by byte, the ranges may overlap
+                for _ in 0..length {
+                    let byte = usize::try_from(target_offset)
+                        .ok()
+                        .and_then(|at| target.get(at).copied())
+                        .ok_or_else(|| invalid("target copy out of bounds"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(invalid("target size mismatch"));
+    }
+    verify("target", checksum(footer + 4), crc32(&target))?;
+
+    Ok(target)
+}
+
+// creates a patch reading unchanged bytes from the source
+// and everything else from the patch itself
+pub fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>> {
+    let mut patch = MAGIC.to_vec();
+    write_number(&mut patch, source.len() as u64);
+    write_number(&mut patch, target.len() as u64);
+    write_number(&mut patch, 0);
+
+    let same = |i: usize| source.get(i) == Some(&target[i]);
+
+    let mut i = 0;
+    while i < target.len() {
+        let kind = same(i);
+        let mut end = i;
+        while end < target.len() && same(end) == kind {
+            end += 1;
+        }
+
+        let length = (end - i) as u64;
+        let command = if kind { SOURCE_READ } else { TARGET_READ };
+        write_number(&mut patch, (length - 1) << 2 | command);
+        if !kind {
+            patch.extend_from_slice(&target[i..end]);
+        }
+        i = end;
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let checksum = crc32(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+
+    Ok(patch)
+}
+
+fn offset(offset: i64, delta: i64) -> Result<i64> {
+    offset
+        .checked_add(delta)
+        .ok_or_else(|| Error::InvalidPatch("BPS offset overflow".to_string()))
+}
+
+fn verify(kind: &'static str, expected: u32, actual: u32) -> Result<()> {
+    if expected != actual {
+        return Err(Error::PatchChecksumMismatch {
+            kind,
+            expected,
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+// numbers are 7 bits per byte, the last byte has the top bit set and
+// every continuation adds one, so each value has a single encoding
+fn write_number(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let bits = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(0x80 | bits);
+            break;
+        }
+
+        output.push(bits);
+        value -= 1;
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| Error::InvalidPatch("truncated BPS patch".to_string()))?;
+
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn number(&mut self) -> Result<u64> {
+        let overflow = || Error::InvalidPatch("BPS number overflow".to_string());
+        let mut value: u64 = 0;
+        let mut shift: u64 = 1;
+
+        loop {
+            let byte = self.bytes(1)?[0];
+            value = (byte as u64 & 0x7F)
+                .checked_mul(shift)
+                .and_then(|bits| value.checked_add(bits))
+                .ok_or_else(overflow)?;
+
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+    }
+
+    // relative offsets store the sign in the lowest bit
+    fn signed(&mut self) -> Result<i64> {
+        let value = self.number()?;
+        let magnitude = (value >> 1) as i64;
+        Ok(if value & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Reader, apply, create, write_number};
+    use crate::checksum::crc32;
+    use crate::error::Error;
+
+    #[test]
+    fn test_numbers() {
+        for value in [0, 1, 127, 128, 255, 16511, 16512, 1 << 40] {
+            let mut data = Vec::new();
+            write_number(&mut data, value);
+
+            let mut reader = Reader {
+                data: &data,
+                pos: 0,
+            };
+            assert_eq!(reader.number().unwrap(), value);
+            assert_eq!(reader.pos, data.len());
+        }
+    }
+
+    #[test]
+    fn test_create_and_apply() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut target = source.clone();
+        target[10] = 0;
+        target[11] = 0;
+        target.extend_from_slice(&[1, 2, 3]);
+
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+        assert_eq!(
+            apply(&source, &create(&source, &[]).unwrap()).unwrap(),
+            vec![]
+        );
+
+        let mut other = source.clone();
+        other[0] = 1;
+        assert!(matches!(
+            apply(&other, &patch),
+            Err(Error::PatchChecksumMismatch { kind: "source", .. })
+        ));
+
+        let mut corrupted = patch.clone();
+        corrupted[8] ^= 0xFF;
+        assert!(matches!(
+            apply(&source, &corrupted),
+            Err(Error::PatchChecksumMismatch { kind: "patch", .. })
+        ));
+    }
+
+    #[test]
+    fn test_copy_actions() {
+        // target copy repeating the first two bytes, then a source copy
+        // going back to the start of the source
+        let mut patch = b"BPS1".to_vec();
+        write_number(&mut patch, 4);
+        write_number(&mut patch, 8);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, (2 - 1) << 2 | 1);
+        patch.extend_from_slice(&[7, 8]);
+        write_number(&mut patch, (4 - 1) << 2 | 3);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, (2 - 1) << 2 | 2);
+        write_number(&mut patch, 2 << 1);
+
+        let source = [1, 2, 3, 4];
+        let target = [7, 8, 7, 8, 7, 8, 3, 4];
+        finish(&mut patch, &source, &target);
+
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn test_huge_target_size() {
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        write_number(&mut patch, 4);
+        write_number(&mut patch, 1 << 60);
+        write_number(&mut patch, 0);
+        finish(&mut patch, &source, &[]);
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(Error::InvalidPatch(_))
+        ));
+
+        // a read going past the declared size fails right away
+        let mut patch = b"BPS1".to_vec();
+        write_number(&mut patch, 4);
+        write_number(&mut patch, 2);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, (4 - 1) << 2);
+        finish(&mut patch, &source, &source);
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(Error::InvalidPatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_huge_metadata_size() {
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        write_number(&mut patch, 4);
+        write_number(&mut patch, 4);
+        write_number(&mut patch, u64::MAX - 1);
+        finish(&mut patch, &source, &source);
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(Error::InvalidPatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_number_overflow() {
+        let source = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        patch.extend_from_slice(&[0x7F; 9]);
+        patch.push(0x80);
+        finish(&mut patch, &source, &source);
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(Error::InvalidPatch(_))
+        ));
+    }
+
+    // appends the checksums of a hand-written patch
+    fn finish(patch: &mut Vec<u8>, source: &[u8], target: &[u8]) {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let checksum = crc32(patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+    }
+}
@@ -0,0 +1,133 @@
+// IPS: a list of records writing bytes at 24-bit offsets,
+// with an optional truncation length after the EOF marker.
+
+use super::Result;
+use crate::error::Error;
+
+pub const MAGIC: &[u8] = b"PATCH";
+const EOF: &[u8] = b"EOF";
+const EOF_OFFSET: usize = 0x454F46;
+const MAX_OFFSET: usize = 0xFFFFFF;
+const MAX_RECORD: usize = 0xFFFF;
+
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let truncated = || Error::InvalidPatch("truncated IPS record".to_string());
+
+    if !patch.starts_with(MAGIC) {
+        return Err(Error::InvalidPatch("missing IPS header".to_string()));
+    }
+
+    let mut target = source.to_vec();
+    let mut pos = MAGIC.len();
+
+    loop {
+        let header = patch.get(pos..pos + 3).ok_or_else(truncated)?;
+        pos += 3;
+        if header == EOF {
+            break;
+        }
+
+        let offset = be(header);
+        let size = be(patch.get(pos..pos + 2).ok_or_else(truncated)?);
+        pos += 2;
+
+        // size 0 is a run of a single repeated byte
+        let data = if size == 0 {
+            let run = patch.get(pos..pos + 3).ok_or_else(truncated)?;
+            pos += 3;
+            vec![run[2]; be(&run[..2])]
+        } else {
+            let data = patch.get(pos..pos + size).ok_or_else(truncated)?;
+            pos += size;
+            data.to_vec()
+        };
+
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    if let Some(length) = patch.get(pos..pos + 3) {
+        target.truncate(be(length));
+    }
+
+    Ok(target)
+}
+
+pub fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>> {
+    if target.len() > MAX_OFFSET {
+        return Err(Error::InvalidPatch(
+            "target is too large for IPS".to_string(),
+        ));
+    }
+
+    let mut patch = MAGIC.to_vec();
+    let differs = |i: usize| source.get(i) != Some(&target[i]);
+
+    let mut i = 0;
+    while i < target.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+
+        // an offset spelling "EOF" would end the patch, start a byte earlier
+        let start = if i == EOF_OFFSET { i - 1 } else { i };
+        let mut end = i;
+        while end < target.len() && end - start < MAX_RECORD && differs(end) {
+            end += 1;
+        }
+
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&target[start..end]);
+        i = end;
+    }
+
+    patch.extend_from_slice(EOF);
+    if target.len() < source.len() {
+        patch.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+
+    Ok(patch)
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply, create};
+
+    #[test]
+    fn test_apply() {
+        let patch = [
+            b'P', b'A', b'T', b'C', b'H', //
+            0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB, // 2 bytes at 1
+            0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xCC, // run of 3 at 5
+            b'E', b'O', b'F',
+        ];
+
+        let target = apply(&[0; 4], &patch).unwrap();
+        assert_eq!(target, vec![0x00, 0xAA, 0xBB, 0x00, 0x00, 0xCC, 0xCC, 0xCC]);
+        assert!(apply(&[0; 4], &patch[..10]).is_err());
+    }
+
+    #[test]
+    fn test_create() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let table: [&[u8]; 4] = [
+            &[1, 9, 9, 4, 5, 6],
+            &[1, 2, 3, 4, 5, 6, 0, 0],
+            &[1, 2, 7],
+            &[],
+        ];
+
+        for target in table {
+            let patch = create(&source, target).unwrap();
+            assert_eq!(apply(&source, &patch).unwrap(), target);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Post-processing against the flicker of XOR drawn sprites. the display
+// is sampled into a per-pixel intensity buffer which frontends present
+// instead of the raw framebuffer.
+//
+// in decay mode `update` is called once per frame: lit pixels go to full
+// intensity and unlit ones fade by the persistence factor, like a CRT
+// phosphor. in blend mode `update` is called after every step and samples
+// the display each time it changed (on draw), a pixel is lit when it was
+// lit in either of the last two samples.
+
+use crate::display::Display;
+use crate::error::Error;
+use crate::render::Color;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    // fraction of the intensity kept per frame, in 1/256th
+    Decay(u8),
+    Blend,
+}
+
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    mode: Mode,
+    width: usize,
+    height: usize,
+    intensity: Vec<u8>,
+
+    // blend mode, the previous sample and the display generation it was
+    // taken at
+    previous: Vec<u8>,
+    generation: Option<u64>,
+}
+
+impl Phosphor {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            width: 0,
+            height: 0,
+            intensity: Vec::new(),
+            previous: Vec::new(),
+            generation: None,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // one byte per pixel, row-major
+    pub fn intensity(&self) -> &[u8] {
+        &self.intensity
+    }
+
+    pub fn update(&mut self, display: &Display) {
+        let (width, height) = (display.width() as usize, display.height() as usize);
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.intensity = vec![0; width * height];
+            self.previous = vec![0; width * height];
+            self.generation = None;
+        }
+
+        match self.mode {
+            Mode::Decay(persistence) => {
+                for (i, level) in self.intensity.iter_mut().enumerate() {
+                    *level = if Self::lit(display, i) {
+                        u8::MAX
+                    } else {
+                        (*level as u16 * persistence as u16 / 256) as u8
+                    };
+                }
+            }
+            Mode::Blend => {
+                if self.generation == Some(display.generation()) {
+                    return;
+                }
+                self.generation = Some(display.generation());
+
+                for (i, (level, previous)) in self
+                    .intensity
+                    .iter_mut()
+                    .zip(self.previous.iter_mut())
+                    .enumerate()
+                {
+                    let current = if Self::lit(display, i) { u8::MAX } else { 0 };
+                    *level = current.max(*previous);
+                    *previous = current;
+                }
+            }
+        }
+    }
+
+    // one byte per pixel
+    pub fn render_grey(&self, out: &mut [u8]) -> Result<()> {
+        Self::check(self.intensity.len(), out.len())?;
+        out[..self.intensity.len()].copy_from_slice(&self.intensity);
+        Ok(())
+    }
+
+    // R, G, B, A bytes per pixel, blended between the two colors
+    pub fn render_rgba(&self, off: Color, on: Color, out: &mut [u8]) -> Result<()> {
+        Self::check(self.intensity.len() * 4, out.len())?;
+        let (out, _) = out.as_chunks_mut::<4>();
+
+        for (pixel, &level) in out.iter_mut().zip(&self.intensity) {
+            for (channel, (&off, &on)) in pixel.iter_mut().zip(off.iter().zip(&on)) {
+                let (off, on, level) = (off as u32, on as u32, level as u32);
+                *channel = ((off * (255 - level) + on * level) / 255) as u8;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lit(display: &Display, index: usize) -> bool {
+        let byte = display.framebuffer()[index / 8];
+        byte & (0x80 >> (index % 8)) != 0
+    }
+
+    fn check(expected: usize, actual: usize) -> Result<()> {
+        if actual < expected {
+            return Err(Error::BufferTooSmall { expected, actual });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mode, Phosphor};
+    use crate::display::Display;
+
+    #[test]
+    fn test_decay() {
+        let mut display = Display::new();
+        let mut phosphor = Phosphor::new(Mode::Decay(128));
+
+        display.draw_sprite(0, 0, &[0x80]);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[0], 255);
+
+        display.draw_sprite(0, 0, &[0x80]);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[0], 127);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[0], 63);
+
+        let mut out = vec![0; 64 * 32 * 4];
+        phosphor
+            .render_rgba([0, 0, 0, 255], [255, 255, 255, 255], &mut out)
+            .unwrap();
+        assert_eq!(out[..4], [63, 63, 63, 255]);
+        assert!(phosphor.render_grey(&mut [0; 64]).is_err());
+    }
+
+    #[test]
+    fn test_blend() {
+        let mut display = Display::new();
+        let mut phosphor = Phosphor::new(Mode::Blend);
+
+        // a sprite moving right by erasing and redrawing it
+        display.draw_sprite(0, 0, &[0x80]);
+        phosphor.update(&display);
+        display.draw_sprite(0, 0, &[0x80]);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[..2], [255, 0]);
+
+        display.draw_sprite(1, 0, &[0x80]);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[..2], [0, 255]);
+
+        // nothing drawn, the sample is kept
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity()[..2], [0, 255]);
+
+        let mut out = vec![0; 64 * 32];
+        phosphor.render_grey(&mut out).unwrap();
+        assert_eq!(out[..2], [0, 255]);
+    }
+}
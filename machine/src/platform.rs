@@ -1,6 +1,8 @@
 use std::time::Duration;
 
-use crate::{display::Display, keyboard::Keyboard};
+use crate::display::{Display, Rect};
+use crate::megachip::{MegaChip, Sample};
+use crate::{keyboard::Keyboard, machine::Machine};
 
 pub enum ExecutionMode {
     Running,
@@ -13,10 +15,54 @@ pub trait Platform {
 
     fn get_keys(&self) -> Keyboard;
 
+    // CHIP-8X second keypad
+    fn get_second_keys(&self) -> Keyboard {
+        Keyboard::new()
+    }
+
+    // CHIP-8X input port, read by FXFB
+    fn get_input_port(&self) -> u8 {
+        0
+    }
+
+    // CHIP-8X output port, called at the end of frames in which FXF8 wrote
+    // to it. the VP-595 uses the value as the pitch of the tone.
+    fn write_port(&mut self, _value: u8) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     fn draw_display(&mut self, display: &Display) -> Result<(), Self::Error>;
+
+    // platforms returning true get draw_changes instead of draw_display,
+    // and only for frames in which the display changed
+    fn only_changed_frames(&self) -> bool {
+        false
+    }
+
+    fn draw_changes(&mut self, display: &Display, _dirty: &[Rect]) -> Result<(), Self::Error> {
+        self.draw_display(display)
+    }
+
+    // draws the presented MegaChip frame instead of the display
+    // while MegaChip mode is on
+    fn draw_megachip(&mut self, _megachip: &MegaChip) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     fn play_sound(&mut self, enabled: bool) -> Result<(), Self::Error>;
 
+    // called at the end of frames in which a MegaChip sample was started
+    // or stopped, None stops the playback
+    fn play_sample(&mut self, _sample: Option<&Sample>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     fn get_time(&self) -> Duration;
 
     fn get_execution_mode(&self) -> ExecutionMode;
+
+    // called at the very end of every Machine::run_frame
+    fn end_frame(&mut self, _machine: &Machine) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
@@ -0,0 +1,285 @@
+// Execution profiler: counts executed instructions per address and per
+// instruction kind, and follows Call/Return with a shadow stack to get
+// inclusive and exclusive counts per subroutine. attach it with
+// Machine::set_profiler.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::instruction::Instruction;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+    // instructions executed in the subroutine and everything it calls
+    pub inclusive: u64,
+    // instructions executed in the subroutine itself
+    pub exclusive: u64,
+}
+
+#[derive(Clone)]
+pub struct Profiler {
+    total: u64,
+    // counts indexed by the full PC, grown as higher addresses run
+    addresses: Vec<u64>,
+    kinds: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<u16, Subroutine>,
+
+    // entry addresses of the active subroutines, outermost first
+    stack: Vec<u16>,
+    // exclusive counts per call stack, for folded output
+    stacks: BTreeMap<Vec<u16>, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            total: 0,
+            addresses: Vec::new(),
+            kinds: BTreeMap::new(),
+            subroutines: BTreeMap::new(),
+            stack: Vec::new(),
+            stacks: BTreeMap::new(),
+        }
+    }
+
+    // records an instruction that was executed successfully at `pc`
+    pub fn record(&mut self, pc: u16, instruction: Instruction) {
+        self.total += 1;
+        let index = pc as usize;
+        if index >= self.addresses.len() {
+            self.addresses.resize(index + 1, 0);
+        }
+        self.addresses[index] += 1;
+        *self.kinds.entry(instruction.name()).or_default() += 1;
+
+        match self.stacks.get_mut(self.stack.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+
+        // recursive subroutines are counted once per instruction
+        for (depth, &addr) in self.stack.iter().enumerate() {
+            if !self.stack[..depth].contains(&addr) {
+                self.subroutines.entry(addr).or_default().inclusive += 1;
+            }
+        }
+        if let Some(&addr) = self.stack.last() {
+            self.subroutines.entry(addr).or_default().exclusive += 1;
+        }
+
+        match instruction {
+            Instruction::Call(addr) => {
+                self.subroutines.entry(addr).or_default().calls += 1;
+                self.stack.push(addr);
+            }
+            // a return without a matching call is ignored, the profiler
+            // may have been attached in the middle of a subroutine
+            Instruction::Return => {
+                self.stack.pop();
+            }
+            _ => (),
+        }
+    }
+
+    // forgets the shadow stack, used when the machine state is replaced
+    pub fn unwind(&mut self) {
+        self.stack.clear();
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn address(&self, addr: u16) -> u64 {
+        self.addresses.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    pub fn kinds(&self) -> &BTreeMap<&'static str, u64> {
+        &self.kinds
+    }
+
+    pub fn subroutines(&self) -> &BTreeMap<u16, Subroutine> {
+        &self.subroutines
+    }
+
+    // one line per call stack, "main;sub_208;sub_20E 12",
+    // the format flamegraph.pl and inferno read
+    pub fn folded(&self) -> String {
+        let mut output = String::new();
+
+        for (stack, count) in &self.stacks {
+            output.push_str("main");
+            for addr in stack {
+                write!(output, ";sub_{:03X}", addr).unwrap();
+            }
+            writeln!(output, " {}", count).unwrap();
+        }
+
+        output
+    }
+
+    // text report with the `limit` hottest addresses, instruction kinds
+    // and subroutines, each sorted by count
+    pub fn report(&self, limit: usize) -> String {
+        let mut output = String::new();
+        let percent = |count: u64| count as f64 * 100.0 / self.total.max(1) as f64;
+
+        writeln!(output, "{} instructions", self.total).unwrap();
+
+        let mut addresses: Vec<(usize, u64)> = self
+            .addresses
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        writeln!(
+            output,
+            "\nhot spots\n{:>8} {:>10} {:>7}",
+            "address", "count", "%"
+        )
+        .unwrap();
+        for (addr, count) in addresses.into_iter().take(limit) {
+            writeln!(
+                output,
+                "{:#8X} {:>10} {:>6.2}%",
+                addr,
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+
+        let mut kinds: Vec<(&str, u64)> = self.kinds.iter().map(|(&k, &v)| (k, v)).collect();
+        kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        writeln!(
+            output,
+            "\ninstructions\n{:<18} {:>10} {:>7}",
+            "kind", "count", "%"
+        )
+        .unwrap();
+        for (kind, count) in kinds.into_iter().take(limit) {
+            writeln!(
+                output,
+                "{:<18} {:>10} {:>6.2}%",
+                kind,
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+
+        let mut subroutines: Vec<(&u16, &Subroutine)> = self.subroutines.iter().collect();
+        subroutines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+
+        writeln!(
+            output,
+            "\nsubroutines\n{:>8} {:>8} {:>10} {:>7} {:>10} {:>7}",
+            "address", "calls", "inclusive", "%", "exclusive", "%"
+        )
+        .unwrap();
+        for (addr, sub) in subroutines.into_iter().take(limit) {
+            writeln!(
+                output,
+                "{:#8X} {:>8} {:>10} {:>6.2}% {:>10} {:>6.2}%",
+                addr,
+                sub.calls,
+                sub.inclusive,
+                percent(sub.inclusive),
+                sub.exclusive,
+                percent(sub.exclusive)
+            )
+            .unwrap();
+        }
+
+        output
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Profiler, Subroutine};
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::program::Program;
+
+    #[test]
+    fn test_subroutines() {
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 1 }, // 0x200
+            Call(0x208),                   // 0x202
+            Call(0x208),                   // 0x204
+            Jump(0x206),                   // 0x206
+            AddImmediate { vx: 0, kk: 1 }, // 0x208
+            Call(0x20E),                   // 0x20A
+            Return,                        // 0x20C
+            Return,                        // 0x20E
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        machine.set_profiler(Some(Profiler::new()));
+
+        for _ in 0..14 {
+            machine.step().unwrap();
+        }
+
+        let profiler = machine.profiler().unwrap();
+        assert_eq!(profiler.total(), 14);
+        assert_eq!(profiler.address(0x206), 3);
+        assert_eq!(profiler.address(0x208), 2);
+        assert_eq!(profiler.kinds()["Call"], 4);
+        assert_eq!(profiler.kinds()["Return"], 4);
+
+        let subroutines = profiler.subroutines();
+        assert_eq!(
+            subroutines[&0x208],
+            Subroutine {
+                calls: 2,
+                inclusive: 8,
+                exclusive: 6
+            }
+        );
+        assert_eq!(
+            subroutines[&0x20E],
+            Subroutine {
+                calls: 2,
+                inclusive: 2,
+                exclusive: 2
+            }
+        );
+
+        assert_eq!(
+            profiler.folded(),
+            "main 6\nmain;sub_208 6\nmain;sub_208;sub_20E 2\n"
+        );
+
+        let report = profiler.report(1);
+        assert!(report.starts_with("14 instructions\n"));
+        assert!(report.contains("   0x206          3  21.43%"));
+    }
+
+    #[test]
+    fn test_high_addresses() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x1208, Clear);
+        profiler.record(0x1208, Clear);
+        profiler.record(0x0208, Clear);
+
+        assert_eq!(profiler.address(0x1208), 2);
+        assert_eq!(profiler.address(0x0208), 1);
+        assert_eq!(profiler.address(0xFFFE), 0);
+    }
+}
@@ -0,0 +1,24 @@
+// Ahead-of-time recompiler: turns a CHIP-8 ROM into Rust source implementing
+// the program as native functions on top of machine::native::Context.
+// other dialects are not decoded, Machine::set_native refuses to run the
+// result on machines configured for them.
+//
+// The generated module exposes `ROM`, `run` (a NativeProgram) and `load`,
+// which loads the ROM into a Machine and attaches the native code to it.
+// Anything the recompiler can't prove statically is left to the interpreter:
+// computed jumps (BNNN), machine code calls (0NNN), code that was never
+// reached during analysis and blocks modified at runtime.
+
+mod analysis;
+mod codegen;
+
+pub use analysis::{Block, ControlFlow, analyse};
+
+// address ROMs are loaded to, see Machine::load_rom
+pub const LOAD_ADDRESS: u16 = 0x200;
+
+// recompiles ROM into the source code of a Rust module,
+// `flow` is the result of analysing the same ROM at LOAD_ADDRESS
+pub fn recompile(rom: &[u8], flow: &ControlFlow) -> String {
+    codegen::emit(rom, LOAD_ADDRESS, flow)
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::instruction::Instruction;
+
+// straight-line run of instructions with a single entry point
+pub struct Block {
+    pub start: u16,
+    pub instructions: Vec<(u16, Instruction)>,
+}
+
+impl Block {
+    // address right after the last instruction of the block
+    pub fn end(&self) -> u16 {
+        self.start.wrapping_add(self.instructions.len() as u16 * 2)
+    }
+}
+
+pub struct ControlFlow {
+    pub blocks: BTreeMap<u16, Block>,
+    // call targets, reported by the tool
+    pub subroutines: BTreeSet<u16>,
+}
+
+// recovers code reachable from the entry point by following every static
+// jump, call and skip target. code behind computed jumps can't be found
+// this way and is left to the interpreter.
+pub fn analyse(rom: &[u8], load_address: u16) -> ControlFlow {
+    let decode = |addr: u16| -> Option<Instruction> {
+        if addr < load_address || !addr.is_multiple_of(2) {
+            return None;
+        }
+
+        let offset = (addr - load_address) as usize;
+        let bytes = rom.get(offset..offset + 2)?;
+        Instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]])).ok()
+    };
+
+    let mut code = BTreeMap::new();
+    let mut leaders = BTreeSet::from([load_address]);
+    let mut subroutines = BTreeSet::new();
+    let mut queue = vec![load_address];
+
+    while let Some(addr) = queue.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+
+        let Some(inst) = decode(addr) else {
+            continue;
+        };
+        code.insert(addr, inst);
+
+        let next = addr.wrapping_add(2);
+        let targets = match inst {
+            Instruction::Jump(target) => vec![target],
+            Instruction::Call(target) => {
+                subroutines.insert(target);
+                vec![target, next]
+            }
+            Instruction::Return | Instruction::JumpOffset(_) => vec![],
+            Instruction::Syscall(_) => vec![next],
+            // the code after a store starts a new block, so entering it
+            // checks again whether the store rewrote it
+            _ if writes_memory(inst) => vec![next],
+            _ if is_skip(inst) => vec![next, next.wrapping_add(2)],
+            _ => {
+                queue.push(next);
+                continue;
+            }
+        };
+
+        for target in targets {
+            leaders.insert(target);
+            queue.push(target);
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter() {
+        let mut instructions = Vec::new();
+        let mut addr = start;
+
+        while let Some(&inst) = code.get(&addr) {
+            if addr != start && leaders.contains(&addr) {
+                break;
+            }
+
+            instructions.push((addr, inst));
+            if ends_block(inst) {
+                break;
+            }
+            match addr.checked_add(2) {
+                Some(next) => addr = next,
+                None => break,
+            }
+        }
+
+        if !instructions.is_empty() {
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    instructions,
+                },
+            );
+        }
+    }
+
+    ControlFlow {
+        blocks,
+        subroutines,
+    }
+}
+
+pub(super) fn is_skip(inst: Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(
+        inst,
+        SkipIfEqualImm { .. }
+            | SkipIfNotEqualImm { .. }
+            | SkipIfEqual { .. }
+            | SkipIfNotEqual { .. }
+            | SkipIfKey(_)
+            | SkipIfNotKey(_)
+    )
+}
+
+fn writes_memory(inst: Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(inst, StoreBcd(_) | StoreRegisters(_) | StoreRange { .. })
+}
+
+fn ends_block(inst: Instruction) -> bool {
+    use Instruction::*;
+
+    is_skip(inst)
+        || writes_memory(inst)
+        || matches!(
+            inst,
+            Jump(_) | Call(_) | Return | JumpOffset(_) | Syscall(_)
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::analyse;
+    use crate::instruction::Instruction::{self, *};
+
+    fn assemble(program: &[Instruction]) -> Vec<u8> {
+        program
+            .iter()
+            .flat_map(|inst| inst.encode().to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_analyse() {
+        let rom = assemble(&[
+            SetImmediate { vx: 0, kk: 1 },   // 0x200
+            Call(0x20A),                     // 0x202
+            SkipIfEqualImm { vx: 0, kk: 2 }, // 0x204
+            Jump(0x202),                     // 0x206
+            JumpOffset(0x300),               // 0x208
+            AddImmediate { vx: 0, kk: 1 },   // 0x20A
+            Return,                          // 0x20C
+            Clear,                           // 0x20E: never reached
+        ]);
+
+        let flow = analyse(&rom, 0x200);
+
+        let starts: Vec<u16> = flow.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+        assert_eq!(flow.blocks[&0x200].end(), 0x202);
+        assert_eq!(flow.blocks[&0x20A].instructions.len(), 2);
+        assert!(flow.subroutines.contains(&0x20A));
+    }
+
+    #[test]
+    fn test_store_ends_block() {
+        let rom = assemble(&[
+            SetIndex(0x206),               // 0x200
+            StoreRegisters(0),             // 0x202
+            StoreBcd(0),                   // 0x204
+            AddImmediate { vx: 1, kk: 2 }, // 0x206
+            Jump(0x208),                   // 0x208
+        ]);
+
+        let flow = analyse(&rom, 0x200);
+
+        let starts: Vec<u16> = flow.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0x200, 0x204, 0x206, 0x208]);
+        assert_eq!(flow.blocks[&0x200].end(), 0x204);
+    }
+
+    #[test]
+    fn test_end_of_address_space() {
+        let mut rom = vec![0; 0xFFFE - 0x200];
+        rom.extend(AddImmediate { vx: 0, kk: 1 }.encode().to_be_bytes());
+
+        let flow = analyse(&rom, 0x200);
+        assert_eq!(flow.blocks[&0xFFFE].instructions.len(), 1);
+    }
+}
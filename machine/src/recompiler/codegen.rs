@@ -0,0 +1,381 @@
+use std::fmt::Write;
+
+use super::analysis::{Block, ControlFlow};
+use crate::instruction::Instruction;
+
+// writes the Rust module for the recovered control flow. every block becomes
+// a function, `run` dispatches on PC until a block hands control back.
+pub fn emit(rom: &[u8], load_address: u16, flow: &ControlFlow) -> String {
+    let mut output = String::new();
+    let out = &mut output;
+
+    let subroutines: Vec<String> = flow
+        .subroutines
+        .iter()
+        .map(|addr| format!("0x{:04X}", addr))
+        .collect();
+
+    writeln!(out, "// Generated by the octochip recompiler, do not edit.").unwrap();
+    writeln!(out, "// ROM size: {} bytes", rom.len()).unwrap();
+    writeln!(out, "// blocks: {}", flow.blocks.len()).unwrap();
+    match subroutines.is_empty() {
+        true => writeln!(out, "// subroutines: none").unwrap(),
+        false => writeln!(out, "// subroutines: {}", subroutines.join(", ")).unwrap(),
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "#![allow(dead_code, unused_imports, clippy::all)]").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use machine::error::Error;").unwrap();
+    writeln!(out, "use machine::instruction::Instruction;").unwrap();
+    writeln!(out, "use machine::machine::Machine;").unwrap();
+    writeln!(out, "use machine::machine::native::{{Context, Exit}};").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "pub const LOAD_ADDRESS: u16 = 0x{:04X};", load_address).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub static ROM: [u8; {}] = [", rom.len()).unwrap();
+    for chunk in rom.chunks(16) {
+        let bytes: Vec<String> = chunk.iter().map(|b| format!("0x{:02X},", b)).collect();
+        writeln!(out, "    {}", bytes.join(" ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(
+        out,
+        "// loads the ROM and attaches the native code to the machine"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn load(machine: &mut Machine) -> Result<(), Error> {{"
+    )
+    .unwrap();
+    writeln!(out, "    machine.load_rom(&ROM)?;").unwrap();
+    writeln!(out, "    machine.set_native(Some(run))").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "pub fn run(ctx: &mut Context<'_>) -> Exit {{").unwrap();
+    writeln!(out, "    loop {{").unwrap();
+    writeln!(out, "        let result = match ctx.pc() {{").unwrap();
+    for start in flow.blocks.keys() {
+        writeln!(out, "            0x{0:04X} => block_{0:04x}(ctx),", start).unwrap();
+    }
+    writeln!(out, "            _ => Err(Exit::Interpret),").unwrap();
+    writeln!(out, "        }};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "        if let Err(exit) = result {{").unwrap();
+    writeln!(out, "            return exit;").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    for block in flow.blocks.values() {
+        writeln!(out).unwrap();
+        emit_block(out, rom, load_address, block);
+    }
+
+    output
+}
+
+fn emit_block(out: &mut String, rom: &[u8], load_address: u16, block: &Block) {
+    let offset = (block.start - load_address) as usize;
+    let length = block.instructions.len() * 2;
+    let code: Vec<String> = rom[offset..offset + length]
+        .iter()
+        .map(|b| format!("0x{:02X}", b))
+        .collect();
+
+    writeln!(
+        out,
+        "fn block_{:04x}(ctx: &mut Context<'_>) -> Result<(), Exit> {{",
+        block.start
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    ctx.enter(0x{:04X}, &[{}])?;",
+        block.start,
+        code.join(", ")
+    )
+    .unwrap();
+
+    let count = block.instructions.len();
+    for (i, &(addr, inst)) in block.instructions.iter().enumerate() {
+        let unexecuted = (count - i) as u32;
+        writeln!(out, "    // 0x{:04X}: {:04X}", addr, inst.encode()).unwrap();
+
+        if emit_instruction(out, addr, unexecuted, inst) {
+            writeln!(out, "}}").unwrap();
+            return;
+        }
+    }
+
+    writeln!(out, "    ctx.jump(0x{:04X});", block.end()).unwrap();
+    writeln!(out, "    Ok(())").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+// emits a single instruction, returns true if it terminated the block
+fn emit_instruction(out: &mut String, addr: u16, unexecuted: u32, inst: Instruction) -> bool {
+    use Instruction::*;
+
+    let next = addr.wrapping_add(2);
+    let skip = |out: &mut String, condition: String| {
+        writeln!(out, "    if {} {{", condition).unwrap();
+        writeln!(out, "        ctx.jump(0x{:04X});", next.wrapping_add(2)).unwrap();
+        writeln!(out, "    }} else {{").unwrap();
+        writeln!(out, "        ctx.jump(0x{:04X});", next).unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "    Ok(())").unwrap();
+        true
+    };
+    let exec = |out: &mut String| {
+        writeln!(
+            out,
+            "    ctx.exec(0x{:04X}, {}, Instruction::{:?})?;",
+            addr, unexecuted, inst
+        )
+        .unwrap();
+        false
+    };
+
+    match inst {
+        // control flow
+        Jump(target) => {
+            writeln!(out, "    ctx.jump(0x{:04X});", target).unwrap();
+            writeln!(out, "    Ok(())").unwrap();
+            true
+        }
+        Call(_) | Return => {
+            exec(out);
+            writeln!(out, "    Ok(())").unwrap();
+            true
+        }
+        // CHIP-8X, MegaChip and CHIP-8E are only decoded by the interpreter
+        JumpOffset(_)
+        | Syscall(_)
+        | CycleBackground
+        | AddNibbles { .. }
+        | SetColorZones { .. }
+        | SetColorRows { .. }
+        | SkipIfKey2(_)
+        | SkipIfNotKey2(_)
+        | OutputPort(_)
+        | InputPort(_)
+        | MegaOff
+        | MegaOn
+        | LongIndex(_)
+        | LoadPalette(_)
+        | SpriteWidth(_)
+        | SpriteHeight(_)
+        | ScreenAlpha(_)
+        | PlaySample(_)
+        | StopSample
+        | BlendMode(_)
+        | CollisionColor(_)
+        | Stop
+        | SkipIfGreater { .. }
+        | StoreRange { .. }
+        | LoadRange { .. }
+        | JumpBack(_)
+        | JumpForward(_)
+        | OutputPort3(_)
+        | WaitInputPort3(_)
+        | InputPort3(_) => {
+            writeln!(
+                out,
+                "    Err(ctx.leave(0x{:04X}, {}, Exit::Interpret))",
+                addr, unexecuted
+            )
+            .unwrap();
+            true
+        }
+
+        // branches
+        SkipIfEqualImm { vx, kk } => skip(out, format!("ctx.v(0x{:X}) == 0x{:02X}", vx, kk)),
+        SkipIfNotEqualImm { vx, kk } => skip(out, format!("ctx.v(0x{:X}) != 0x{:02X}", vx, kk)),
+        SkipIfEqual { vx, vy } => skip(out, format!("ctx.v(0x{:X}) == ctx.v(0x{:X})", vx, vy)),
+        SkipIfNotEqual { vx, vy } => skip(out, format!("ctx.v(0x{:X}) != ctx.v(0x{:X})", vx, vy)),
+        SkipIfKey(vx) => skip(out, format!("ctx.keys().is_key_pressed(ctx.v(0x{:X}))", vx)),
+        SkipIfNotKey(vx) => skip(
+            out,
+            format!("!ctx.keys().is_key_pressed(ctx.v(0x{:X}))", vx),
+        ),
+
+        // registers and ALU
+        SetImmediate { vx, kk } => {
+            writeln!(out, "    ctx.set_v(0x{:X}, 0x{:02X});", vx, kk).unwrap();
+            false
+        }
+        AddImmediate { vx, kk } => {
+            writeln!(
+                out,
+                "    ctx.set_v(0x{0:X}, ctx.v(0x{0:X}).wrapping_add(0x{1:02X}));",
+                vx, kk
+            )
+            .unwrap();
+            false
+        }
+        Set { vx, vy } => {
+            writeln!(out, "    ctx.set_v(0x{:X}, ctx.v(0x{:X}));", vx, vy).unwrap();
+            false
+        }
+        Or { vx, vy } | And { vx, vy } | Xor { vx, vy } => {
+            let op = match inst {
+                Or { .. } => "|",
+                And { .. } => "&",
+                _ => "^",
+            };
+            writeln!(
+                out,
+                "    ctx.set_v(0x{0:X}, ctx.v(0x{0:X}) {2} ctx.v(0x{1:X}));",
+                vx, vy, op
+            )
+            .unwrap();
+            false
+        }
+        // VF is written before Vx, same as the interpreter does
+        Add { vx, vy } => {
+            writeln!(out, "    {{").unwrap();
+            writeln!(
+                out,
+                "        let (result, carry) = ctx.v(0x{:X}).overflowing_add(ctx.v(0x{:X}));",
+                vx, vy
+            )
+            .unwrap();
+            writeln!(out, "        ctx.set_v(0xF, carry as u8);").unwrap();
+            writeln!(out, "        ctx.set_v(0x{:X}, result);", vx).unwrap();
+            writeln!(out, "    }}").unwrap();
+            false
+        }
+        Subtract { vx, vy } | SubtractNegate { vx, vy } => {
+            let (a, b) = match inst {
+                Subtract { .. } => ("x", "y"),
+                _ => ("y", "x"),
+            };
+            writeln!(out, "    {{").unwrap();
+            writeln!(
+                out,
+                "        let (x, y) = (ctx.v(0x{:X}), ctx.v(0x{:X}));",
+                vx, vy
+            )
+            .unwrap();
+            writeln!(out, "        ctx.set_v(0xF, ({} >= {}) as u8);", a, b).unwrap();
+            writeln!(
+                out,
+                "        ctx.set_v(0x{:X}, {}.wrapping_sub({}));",
+                vx, a, b
+            )
+            .unwrap();
+            writeln!(out, "    }}").unwrap();
+            false
+        }
+
+        // timers and input
+        LoadDelayTimer(vx) => {
+            writeln!(out, "    ctx.set_v(0x{:X}, ctx.delay_timer());", vx).unwrap();
+            false
+        }
+        SetDelayTimer(vx) => {
+            writeln!(out, "    ctx.set_delay_timer(ctx.v(0x{:X}));", vx).unwrap();
+            false
+        }
+        SetSoundTimer(vx) => {
+            writeln!(out, "    ctx.set_sound_timer(ctx.v(0x{:X}));", vx).unwrap();
+            false
+        }
+        WaitForKey(vx) => {
+            writeln!(
+                out,
+                "    ctx.wait_for_key(0x{:04X}, {}, 0x{:X})?;",
+                addr, unexecuted, vx
+            )
+            .unwrap();
+            false
+        }
+
+        // everything touching memory, the display or quirks
+        // goes through the interpreter handlers
+        Clear
+        | SetIndex(_)
+        | AddIndex(_)
+        | ShiftRight { .. }
+        | ShiftLeft { .. }
+        | Rnd { .. }
+        | Draw { .. }
+        | LoadFont(_)
+        | StoreBcd(_)
+        | StoreRegisters(_)
+        | LoadRegisters(_) => exec(out),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::emit;
+    use crate::instruction::Instruction::{self, *};
+    use crate::recompiler::analyse;
+
+    fn assemble(program: &[Instruction]) -> Vec<u8> {
+        program
+            .iter()
+            .flat_map(|inst| inst.encode().to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_emit() {
+        let rom = assemble(&[
+            SetImmediate { vx: 0, kk: 5 },   // 0x200
+            AddImmediate { vx: 0, kk: 1 },   // 0x202
+            SkipIfEqualImm { vx: 0, kk: 8 }, // 0x204
+            Jump(0x202),                     // 0x206
+            Draw { vx: 0, vy: 0, n: 1 },     // 0x208
+            Jump(0x208),                     // 0x20A
+        ]);
+
+        let source = emit(&rom, 0x200, &analyse(&rom, 0x200));
+        let lines: Vec<&str> = source.lines().collect();
+
+        assert!(lines.contains(&"pub static ROM: [u8; 12] = ["));
+        for start in ["0200", "0202", "0206", "0208"] {
+            let entry = format!("            0x{} => block_{}(ctx),", start, start);
+            assert!(lines.contains(&entry.as_str()), "no dispatch to {}", start);
+        }
+
+        // the loop body runs on until the skip ends it
+        let block = &source[source.find("fn block_0202").unwrap()..];
+        let block = &block[..block.find("\n}\n").unwrap()];
+        assert_eq!(
+            block.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "    ctx.enter(0x0202, &[0x70, 0x01, 0x30, 0x08])?;",
+                "    // 0x0202: 7001",
+                "    ctx.set_v(0x0, ctx.v(0x0).wrapping_add(0x01));",
+                "    // 0x0204: 3008",
+                "    if ctx.v(0x0) == 0x08 {",
+                "        ctx.jump(0x0208);",
+                "    } else {",
+                "        ctx.jump(0x0206);",
+                "    }",
+                "    Ok(())",
+            ]
+        );
+
+        // drawing goes through the interpreter handler
+        assert!(
+            lines.contains(&"    ctx.exec(0x0208, 2, Instruction::Draw { vx: 0, vy: 0, n: 1 })?;")
+        );
+    }
+
+    #[test]
+    fn test_skip_at_end_of_address_space() {
+        let mut rom = vec![0; 0xFFFC - 0x200];
+        rom.extend(SkipIfEqualImm { vx: 0, kk: 0 }.encode().to_be_bytes());
+
+        let source = emit(&rom, 0x200, &analyse(&rom, 0x200));
+        assert!(source.contains("        ctx.jump(0x0000);"));
+    }
+}
@@ -0,0 +1,389 @@
+// Converts 1-bit framebuffers into RGBA pixels for frontends. several
+// framebuffers can be rendered as bit planes, the bits of a pixel in each
+// plane form its palette index. the output is scaled by an integer factor
+// and surrounded by an optional border.
+//
+// CHIP-8X displays are rendered with the colors of the VP-590 board from
+// the display's color attributes instead of a palette.
+//
+// u8 buffers hold R, G, B, A bytes. u32 buffers hold the same bytes in
+// memory order, so either view of a canvas ImageData works.
+
+use crate::display::Display;
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub type Color = [u8; 4];
+
+// VP-590 foreground colors by CHIP-8X color code
+pub const VP590_FOREGROUND: [Color; 8] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0x00, 0xFF],
+    [0x00, 0x00, 0xFF, 0xFF],
+    [0xFF, 0x00, 0xFF, 0xFF],
+    [0x00, 0xFF, 0x00, 0xFF],
+    [0xFF, 0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+];
+
+// VP-590 background colors, in the order 02A0 cycles through them
+pub const VP590_BACKGROUND: [Color; 4] = [
+    [0x00, 0x00, 0x80, 0xFF],
+    [0x00, 0x00, 0x00, 0xFF],
+    [0x00, 0x80, 0x00, 0xFF],
+    [0x80, 0x00, 0x00, 0xFF],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Color>,
+}
+
+impl Palette {
+    // one color per combination of plane bits, 2, 4, 8 or 16 colors
+    pub fn new(colors: &[Color]) -> Result<Self> {
+        if !matches!(colors.len(), 2 | 4 | 8 | 16) {
+            return Err(Error::InvalidPalette(colors.len()));
+        }
+
+        Ok(Self {
+            colors: colors.to_vec(),
+        })
+    }
+
+    pub fn monochrome(off: Color, on: Color) -> Self {
+        Self {
+            colors: vec![off, on],
+        }
+    }
+
+    // XO-CHIP colors used by Octo
+    pub fn octo() -> Self {
+        Self {
+            colors: vec![
+                [0x99, 0x66, 0x00, 0xFF],
+                [0xFF, 0xCC, 0x00, 0xFF],
+                [0xFF, 0x66, 0x00, 0xFF],
+                [0x66, 0x22, 0x00, 0xFF],
+            ],
+        }
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn planes(&self) -> usize {
+        self.colors.len().trailing_zeros() as usize
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::monochrome([0x00, 0x00, 0x00, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    palette: Palette,
+    scale: usize,
+    border: usize,
+    border_color: Color,
+}
+
+impl Renderer {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            scale: 1,
+            border: 0,
+            border_color: [0x00, 0x00, 0x00, 0xFF],
+        }
+    }
+
+    pub fn with_scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    // border width is in output pixels
+    pub fn with_border(mut self, width: usize, color: Color) -> Self {
+        self.border = width;
+        self.border_color = color;
+        self
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    // output size in pixels for a framebuffer of the given size
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        (
+            width * self.scale + 2 * self.border,
+            height * self.scale + 2 * self.border,
+        )
+    }
+
+    pub fn render_u32(&self, display: &Display, out: &mut [u32]) -> Result<()> {
+        let (width, height) = (display.width() as usize, display.height() as usize);
+        self.render_planes_u32(&[display.framebuffer()], width, height, out)
+    }
+
+    pub fn render_rgba(&self, display: &Display, out: &mut [u8]) -> Result<()> {
+        let (width, height) = (display.width() as usize, display.height() as usize);
+        self.render_planes_rgba(&[display.framebuffer()], width, height, out)
+    }
+
+    pub fn render_color_u32(&self, display: &Display, out: &mut [u32]) -> Result<()> {
+        let border = u32::from_ne_bytes(self.border_color);
+        self.render_color(display, u32::from_ne_bytes, border, out)
+    }
+
+    pub fn render_color_rgba(&self, display: &Display, out: &mut [u8]) -> Result<()> {
+        let (out, _) = out.as_chunks_mut::<4>();
+        self.render_color(display, |color| color, self.border_color, out)
+    }
+
+    pub fn render_planes_u32(
+        &self,
+        planes: &[&[u8]],
+        width: usize,
+        height: usize,
+        out: &mut [u32],
+    ) -> Result<()> {
+        // palettes have at most 16 colors, no allocation per frame
+        let mut colors = [0; 16];
+        let colors = &mut colors[..self.palette.colors.len()];
+        for (color, &rgba) in colors.iter_mut().zip(&self.palette.colors) {
+            *color = u32::from_ne_bytes(rgba);
+        }
+
+        let border = u32::from_ne_bytes(self.border_color);
+        self.render(planes, width, height, colors, border, out)
+    }
+
+    pub fn render_planes_rgba(
+        &self,
+        planes: &[&[u8]],
+        width: usize,
+        height: usize,
+        out: &mut [u8],
+    ) -> Result<()> {
+        let (out, _) = out.as_chunks_mut::<4>();
+        self.render(
+            planes,
+            width,
+            height,
+            &self.palette.colors,
+            self.border_color,
+            out,
+        )
+    }
+
+    fn render<T: Copy>(
+        &self,
+        planes: &[&[u8]],
+        width: usize,
+        height: usize,
+        colors: &[T],
+        border: T,
+        out: &mut [T],
+    ) -> Result<()> {
+        if planes.len() != self.palette.planes() {
+            return Err(Error::PlaneMismatch {
+                expected: self.palette.planes(),
+                actual: planes.len(),
+            });
+        }
+
+        let row_bytes = width.div_ceil(8);
+        if planes.iter().any(|plane| plane.len() < row_bytes * height) {
+            return Err(Error::BufferTooSmall {
+                expected: row_bytes * height,
+                actual: planes.iter().map(|plane| plane.len()).min().unwrap_or(0),
+            });
+        }
+
+        self.fill(width, height, border, out, |x, y| {
+            let offset = y * row_bytes + x / 8;
+            let bit = 7 - x % 8;
+
+            let mut index = 0;
+            for (i, plane) in planes.iter().enumerate() {
+                index |= ((plane[offset] >> bit) as usize & 1) << i;
+            }
+            colors[index]
+        })
+    }
+
+    fn render_color<T: Copy>(
+        &self,
+        display: &Display,
+        convert: impl Fn(Color) -> T,
+        border: T,
+        out: &mut [T],
+    ) -> Result<()> {
+        let foreground = VP590_FOREGROUND.map(&convert);
+        let background = convert(VP590_BACKGROUND[display.background() as usize]);
+        let (width, height) = (display.width() as usize, display.height() as usize);
+
+        self.fill(width, height, border, out, |x, y| {
+            let (x, y) = (x as u8, y as u8);
+            match display.get_pixel(x, y) {
+                true => foreground[display.color(x, y) as usize],
+                false => background,
+            }
+        })
+    }
+
+    // scales the pixels given by `pixel` into out and draws the border
+    fn fill<T: Copy>(
+        &self,
+        width: usize,
+        height: usize,
+        border: T,
+        out: &mut [T],
+        pixel: impl Fn(usize, usize) -> T,
+    ) -> Result<()> {
+        let (out_width, out_height) = self.size(width, height);
+        let expected = out_width * out_height;
+        if out.len() < expected {
+            return Err(Error::BufferTooSmall {
+                expected,
+                actual: out.len(),
+            });
+        }
+
+        let out = &mut out[..expected];
+        let (scale, edge) = (self.scale, self.border);
+        let bottom = (edge + height * scale) * out_width;
+
+        out[..edge * out_width].fill(border);
+        out[bottom..].fill(border);
+
+        for y in 0..height {
+            let start = (edge + y * scale) * out_width;
+            let row = &mut out[start..start + out_width];
+            row[..edge].fill(border);
+            row[out_width - edge..].fill(border);
+
+            let pixels = &mut row[edge..out_width - edge];
+            for (x, chunk) in pixels.chunks_exact_mut(scale).enumerate() {
+                chunk.fill(pixel(x, y));
+            }
+
+            for line in 1..scale {
+                out.copy_within(start..start + out_width, start + line * out_width);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new(Palette::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Palette, Renderer, VP590_BACKGROUND, VP590_FOREGROUND};
+    use crate::display::{Display, Rect};
+    use crate::error::Error;
+
+    const OFF: [u8; 4] = [0, 0, 0, 255];
+    const ON: [u8; 4] = [255, 255, 255, 255];
+    const EDGE: [u8; 4] = [1, 2, 3, 4];
+
+    #[test]
+    fn test_render_display() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 0, &[0x80]);
+        display.draw_sprite(63, 31, &[0x80]);
+
+        let renderer = Renderer::default().with_scale(2).with_border(1, EDGE);
+        assert_eq!(renderer.size(64, 32), (130, 66));
+
+        let mut out = vec![0; 130 * 66 * 4];
+        renderer.render_rgba(&display, &mut out).unwrap();
+
+        let pixel = |x: usize, y: usize| {
+            let i = (y * 130 + x) * 4;
+            [out[i], out[i + 1], out[i + 2], out[i + 3]]
+        };
+        assert_eq!(pixel(0, 0), EDGE);
+        assert_eq!(pixel(129, 65), EDGE);
+        assert_eq!(pixel(1, 1), ON);
+        assert_eq!(pixel(2, 2), ON);
+        assert_eq!(pixel(3, 2), OFF);
+        assert_eq!(pixel(128, 64), ON);
+        assert_eq!(pixel(127, 63), ON);
+        assert_eq!(pixel(126, 63), OFF);
+
+        let mut small = vec![0; 64 * 32];
+        assert!(
+            Renderer::default()
+                .with_scale(2)
+                .render_u32(&display, &mut small)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render_planes() {
+        let colors = [OFF, ON, EDGE, [9, 9, 9, 9]];
+        let renderer = Renderer::new(Palette::new(&colors).unwrap());
+
+        let first = [0b1010_0000];
+        let second = [0b0110_0000];
+        let mut out = [0u32; 8];
+        renderer
+            .render_planes_u32(&[&first, &second], 8, 1, &mut out)
+            .unwrap();
+
+        let expected = [1, 2, 3, 0, 0, 0, 0, 0].map(|i| u32::from_ne_bytes(colors[i]));
+        assert_eq!(out, expected);
+
+        assert!(matches!(
+            renderer.render_planes_u32(&[&first], 8, 1, &mut out),
+            Err(Error::PlaneMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        assert!(matches!(
+            Palette::new(&colors[..3]),
+            Err(Error::InvalidPalette(3))
+        ));
+    }
+
+    #[test]
+    fn test_render_color() {
+        let mut display = Display::new();
+        display.draw_sprite(8, 0, &[0xC0]);
+        display.set_color(
+            Rect {
+                x: 9,
+                y: 0,
+                width: 1,
+                height: 1,
+            },
+            4,
+        );
+        display.cycle_background();
+
+        let mut out = [0u32; 64 * 32];
+        Renderer::default()
+            .render_color_u32(&display, &mut out)
+            .unwrap();
+        assert_eq!(out[8], u32::from_ne_bytes(VP590_FOREGROUND[4]));
+        assert_eq!(out[9], u32::from_ne_bytes(VP590_FOREGROUND[4]));
+        assert_eq!(out[10], u32::from_ne_bytes(VP590_BACKGROUND[1]));
+    }
+}
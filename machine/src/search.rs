@@ -0,0 +1,242 @@
+// Memory search for reverse engineering: find where a game keeps its score
+// or lives by snapshotting memory and narrowing down candidate addresses
+// with comparisons between consecutive snapshots.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::Error;
+use crate::machine::Machine;
+use crate::memory::Memory;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    // current value is equal to the given one
+    Equal(u8),
+    // compared to the previous snapshot
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    // current minus previous value is equal to the given difference
+    ChangedBy(i16),
+}
+
+impl Comparison {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            Comparison::Equal(value) => current == value,
+            Comparison::Changed => current != previous,
+            Comparison::Unchanged => current == previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+            Comparison::ChangedBy(delta) => current as i16 - previous as i16 == delta,
+        }
+    }
+}
+
+pub struct Search {
+    // memory as it was at the last filter
+    previous: Vec<u8>,
+    // addresses still matching every filter, sorted
+    candidates: Vec<u32>,
+    // register values seen at watched program counters
+    watches: BTreeMap<u16, [BTreeSet<u8>; 16]>,
+}
+
+impl Search {
+    // starts a search with every address as a candidate
+    pub fn new(memory: &Memory) -> Self {
+        let previous = memory.as_slice().to_vec();
+        let candidates = (0..previous.len() as u32).collect();
+
+        Self {
+            previous,
+            candidates,
+            watches: BTreeMap::new(),
+        }
+    }
+
+    pub fn candidates(&self) -> &[u32] {
+        &self.candidates
+    }
+
+    // keeps candidates matching the comparison and takes a new snapshot,
+    // returns the number of candidates left. memory has to be the same
+    // size as when the search started.
+    pub fn filter(&mut self, memory: &Memory, comparison: Comparison) -> Result<usize> {
+        let current = self.current(memory)?;
+
+        self.candidates.retain(|&addr| {
+            let addr = addr as usize;
+            comparison.matches(self.previous[addr], current[addr])
+        });
+        self.previous.copy_from_slice(current);
+
+        Ok(self.candidates.len())
+    }
+
+    // starts recording register values whenever the instruction at pc
+    // is about to be executed by Search::step
+    pub fn watch(&mut self, pc: u16) {
+        self.watches.entry(pc).or_default();
+    }
+
+    // records registers if the machine is at a watched program counter
+    pub fn observe(&mut self, machine: &Machine) {
+        if let Some(seen) = self.watches.get_mut(&machine.get_pc()) {
+            for (values, &value) in seen.iter_mut().zip(machine.get_registers()) {
+                values.insert(value);
+            }
+        }
+    }
+
+    // executes a single instruction, observing registers before it
+    pub fn step(&mut self, machine: &mut Machine) -> Result<bool> {
+        self.observe(machine);
+        machine.step()
+    }
+
+    // values Vx had at a watched program counter
+    pub fn seen_values(&self, pc: u16, vx: u8) -> Option<&BTreeSet<u8>> {
+        self.watches.get(&pc).map(|seen| &seen[vx as usize])
+    }
+
+    // keeps candidates whose current value was seen in Vx at the watched pc,
+    // e.g. the register a game loads the lives counter into before drawing it
+    pub fn filter_by_register(&mut self, memory: &Memory, pc: u16, vx: u8) -> Result<usize> {
+        let empty = BTreeSet::new();
+        let seen = match self.watches.get(&pc) {
+            Some(seen) => &seen[vx as usize],
+            None => &empty,
+        };
+        let current = self.current(memory)?;
+
+        self.candidates
+            .retain(|&addr| seen.contains(&current[addr as usize]));
+        self.previous.copy_from_slice(current);
+
+        Ok(self.candidates.len())
+    }
+
+    // lists candidates with their values in the last snapshot
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+
+        for &addr in self.candidates.iter() {
+            let value = self.previous[addr as usize];
+            output.push_str(&format!("0x{:04X}: 0x{:02X} ({})\n", addr, value, value));
+        }
+
+        output
+    }
+
+    fn current<'a>(&self, memory: &'a Memory) -> Result<&'a [u8]> {
+        let current = memory.as_slice();
+        if current.len() != self.previous.len() {
+            return Err(Error::MemorySizeChanged {
+                expected: self.previous.len(),
+                actual: current.len(),
+            });
+        }
+
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Comparison, Search};
+    use crate::error::Error;
+    use crate::font::Font;
+    use crate::instruction::Instruction::*;
+    use crate::machine::Machine;
+    use crate::memory::{Memory, MemoryMap};
+    use crate::program::Program;
+
+    #[test]
+    fn test_filter() {
+        // lives at 0x300 go down by one on every iteration
+        let program = Program(vec![
+            SetImmediate { vx: 0, kk: 3 },
+            SetIndex(0x300),
+            StoreRegisters(0),
+            LoadRegisters(0),
+            AddImmediate { vx: 0, kk: 0xFF },
+            StoreRegisters(0),
+            Jump(0x206),
+        ]);
+
+        let mut machine = Machine::new();
+        machine.load_program(program.into()).unwrap();
+        for _ in 0..3 {
+            machine.step().unwrap();
+        }
+
+        let mut search = Search::new(machine.get_memory());
+        search.watch(0x208);
+
+        search
+            .filter(machine.get_memory(), Comparison::Equal(3))
+            .unwrap();
+        assert!(search.candidates().contains(&0x300));
+
+        for _ in 0..4 {
+            search.step(&mut machine).unwrap();
+        }
+        search
+            .filter(machine.get_memory(), Comparison::Decreased)
+            .unwrap();
+        assert_eq!(search.candidates(), &[0x300]);
+
+        for _ in 0..4 {
+            search.step(&mut machine).unwrap();
+        }
+        assert_eq!(
+            search
+                .filter(machine.get_memory(), Comparison::ChangedBy(-1))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            search
+                .filter(machine.get_memory(), Comparison::Unchanged)
+                .unwrap(),
+            1
+        );
+
+        // V0 held 3 and 2 when loaded at 0x208, memory holds 1 now
+        assert_eq!(search.seen_values(0x208, 0).unwrap().len(), 2);
+        assert_eq!(
+            search
+                .filter_by_register(machine.get_memory(), 0x208, 0)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_megachip_memory() {
+        let font = Font::default();
+        let mut memory = Memory::with_map(&MemoryMap::MEGACHIP, &font);
+        memory.write(0x10300, 5).unwrap();
+
+        let mut search = Search::new(&memory);
+        memory.write(0x10300, 4).unwrap();
+        memory.write(0x300, 6).unwrap();
+
+        // 0x10300 doesn't alias 0x300 as it would with 16-bit addresses
+        search.filter(&memory, Comparison::Decreased).unwrap();
+        assert_eq!(search.candidates(), &[0x10300]);
+
+        let small = Memory::with_map(&MemoryMap::COSMAC_VIP, &font);
+        assert!(matches!(
+            search.filter(&small, Comparison::Unchanged),
+            Err(Error::MemorySizeChanged {
+                expected: 0x1000000,
+                actual: 0x1000
+            })
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+// COSMAC VIP backend: the CHIP-8 program is run by the original
+// interpreter on an emulated CDP1802, instead of by Machine::exec. the
+// interpreter is user supplied and loaded below the program, the
+// CDP1861 fetches the display page by DMA into the Display and the Q
+// output drives the tone generator.
+//
+// the monitor ROM is not emulated. the machine starts the way the
+// monitor hands over to RAM: R0 as program counter at 0x0000 and the
+// highest RAM page in R1.1. of the ROM only page 0x81 is present, FX29
+// reads the hex digit patterns from it. it is built from the configured
+// font, with the table of glyph offsets the VIP interpreter expects.
+
+pub mod cdp1802;
+pub mod cdp1861;
+
+use crate::display::Display;
+use crate::font::Font;
+use crate::keyboard::Keyboard;
+use crate::memory::Memory;
+use cdp1802::{Bus, Cdp1802};
+use cdp1861::{Cdp1861, LINE_BYTES, LINE_CYCLES, VISIBLE_LINES};
+
+// the 1802 of the VIP runs at 1.7609 MHz, 8 clocks per machine cycle
+pub const CYCLES_PER_SECOND: u32 = 1_760_900 / 8;
+
+const ROM_START: u16 = 0x8000;
+const FONT_PAGE: u16 = 0x8100;
+
+#[derive(Clone)]
+pub struct Vip {
+    cpu: Cdp1802,
+    video: Cdp1861,
+    interpreter: Vec<u8>,
+    font_page: [u8; 256],
+    // key selected with OUT 2, EF3 reports whether it is pressed
+    latch: u8,
+    // machine cycles left in the current line, negative when the last
+    // instruction ran into the next one
+    line_cycles: i32,
+}
+
+impl Vip {
+    pub fn new(interpreter: &[u8]) -> Self {
+        Self {
+            cpu: Cdp1802::new(),
+            video: Cdp1861::new(),
+            interpreter: interpreter.to_vec(),
+            font_page: [0; 256],
+            latch: 0,
+            line_cycles: 0,
+        }
+    }
+
+    pub fn interpreter(&self) -> &[u8] {
+        &self.interpreter
+    }
+
+    pub fn cpu(&self) -> &Cdp1802 {
+        &self.cpu
+    }
+
+    pub fn video(&self) -> &Cdp1861 {
+        &self.video
+    }
+
+    // the speaker sounds while Q is set
+    pub fn tone(&self) -> bool {
+        self.cpu.q
+    }
+
+    // copies the interpreter to 0x0000 and powers the system up
+    pub fn reset(&mut self, memory: &mut Memory, font: &Font) {
+        for (addr, &byte) in self.interpreter.iter().enumerate() {
+            let _ = memory.write(addr as u32, byte);
+        }
+
+        self.font_page = [0; 256];
+        for (digit, offset) in self.font_page[..16].iter_mut().enumerate() {
+            *offset = 0x10 + digit as u8 * 5;
+        }
+        let glyphs = font.small().iter().take(256 - 0x10);
+        for (byte, &glyph) in self.font_page[0x10..].iter_mut().zip(glyphs) {
+            *byte = glyph;
+        }
+
+        self.cpu = Cdp1802::new();
+        // the VIP takes at most 32 KiB of RAM below the ROM
+        let pages = memory.size().min(ROM_START as usize) / 0x100;
+        self.cpu.r[1] = (pages.saturating_sub(1) << 8) as u16;
+        self.video = Cdp1861::new();
+        self.latch = 0;
+        self.line_cycles = 0;
+    }
+
+    // executes one CPU instruction, lines start and end between
+    // instructions
+    pub fn step(&mut self, memory: &mut Memory, display: &mut Display, keys: Keyboard) {
+        if self.line_cycles <= 0 {
+            self.start_line(memory, display, keys);
+        }
+
+        let (cpu, mut bus) = self.split(memory, keys);
+        let cycles = cpu.step(&mut bus);
+        self.line_cycles -= cycles as i32;
+
+        if self.line_cycles <= 0 {
+            self.video.advance();
+        }
+    }
+
+    // runs until the last line of the frame is finished
+    pub fn run_frame(&mut self, memory: &mut Memory, display: &mut Display, keys: Keyboard) {
+        loop {
+            self.step(memory, display, keys);
+            if self.video.line() == 0 && self.line_cycles <= 0 {
+                break;
+            }
+        }
+    }
+
+    fn start_line(&mut self, memory: &mut Memory, display: &mut Display, keys: Keyboard) {
+        self.line_cycles += LINE_CYCLES as i32;
+
+        if self.video.interrupt() {
+            self.cpu.interrupt();
+        }
+
+        let Some(line) = self.video.visible_line() else {
+            return;
+        };
+
+        // the visible lines are spread over the rows of the display,
+        // the interpreter repeats each of its rows for several lines
+        let row = line as usize * display.height() as usize / VISIBLE_LINES as usize;
+        let row_bytes = display.width() as usize / 8;
+        let (cpu, mut bus) = self.split(memory, keys);
+        for column in 0..LINE_BYTES as usize {
+            let byte = cpu.dma_out(&mut bus);
+            if column < row_bytes {
+                display.write_byte(row * row_bytes + column, byte);
+            }
+        }
+        self.line_cycles -= LINE_BYTES as i32;
+    }
+
+    // the CPU and the rest of the system as seen from it
+    fn split<'a>(
+        &'a mut self,
+        memory: &'a mut Memory,
+        keys: Keyboard,
+    ) -> (&'a mut Cdp1802, VipBus<'a>) {
+        let bus = VipBus {
+            memory,
+            video: &mut self.video,
+            font_page: &self.font_page,
+            latch: &mut self.latch,
+            keys,
+        };
+        (&mut self.cpu, bus)
+    }
+}
+
+struct VipBus<'a> {
+    memory: &'a mut Memory,
+    video: &'a mut Cdp1861,
+    font_page: &'a [u8; 256],
+    latch: &'a mut u8,
+    keys: Keyboard,
+}
+
+impl Bus for VipBus<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            FONT_PAGE..=0x81FF => self.font_page[addr as usize & 0xFF],
+            ROM_START.. => 0,
+            // RAM is mirrored up to the ROM
+            _ => {
+                let addr = addr as usize % self.memory.size();
+                self.memory.read(addr as u32).unwrap_or(0)
+            }
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr < ROM_START {
+            let addr = addr as usize % self.memory.size();
+            let _ = self.memory.write(addr as u32, value);
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.video.set_enabled(false),
+            2 => *self.latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.video.set_enabled(true);
+        }
+        0
+    }
+
+    fn flag(&self, line: u8) -> bool {
+        match line {
+            1 => self.video.flag(),
+            3 => self.keys.is_key_pressed(*self.latch),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Vip;
+    use crate::error::Error;
+    use crate::font::Font;
+    use crate::keyboard::Keyboard;
+    use crate::machine::Machine;
+    use crate::machine::config::{Config, Profile};
+    use crate::memory::{Memory, MemoryMap};
+
+    #[test]
+    fn test_vip() {
+        let mut interpreter = vec![0; 0x200];
+        let main = [
+            0xF8, 0x04, 0xA3, 0xD3, // continue with R3 as program counter
+            0xF8, 0x00, 0xB1, 0xF8, 0x41, 0xA1, // R1 = 0x0041
+            0xF8, 0x00, 0xB2, 0xF8, 0x7F, 0xA2, // R2 = 0x007F
+            0xE2, 0x69, // display on
+            0xF8, 0x05, 0x52, 0x62, 0x22, // latch key 5
+            0x3E, 0x1A, // skip SEQ when it is up
+            0x7B, // tone on
+            0x30, 0x1A, // loop
+        ];
+        let interrupt = [
+            0x70, // return
+            0x22, 0x78, // save X and P
+            0xF8, 0x01, 0xB0, 0xF8, 0x00, 0xA0, // DMA from 0x0100
+            0x30, 0x40, // back to the return
+        ];
+        interpreter[..main.len()].copy_from_slice(&main);
+        interpreter[0x40..0x40 + interrupt.len()].copy_from_slice(&interrupt);
+        // four lines per row, the last one is shown
+        interpreter[0x100] = 0x55;
+        interpreter[0x118] = 0xAA;
+
+        let mut machine = Machine::with_config(Config::for_profile(Profile::CosmacVip));
+        machine.set_vip(Some(Vip::new(&interpreter))).unwrap();
+        machine.load_rom(&[0x12, 0x00]).unwrap();
+        machine.set_keys(Keyboard::from(1 << 5));
+        machine.step_frame().unwrap();
+        machine.step_frame().unwrap();
+
+        let vip = machine.get_vip().unwrap();
+        assert!(vip.video().is_enabled());
+        assert!(vip.tone());
+        assert_eq!(vip.cpu().r[0], 0x0500);
+        assert_eq!(machine.get_display().framebuffer()[0], 0xAA);
+        assert_eq!(machine.get_memory().read(0x200).unwrap(), 0x12);
+
+        let error = machine.set_vip(Some(Vip::new(&[0; 0x201]))).unwrap_err();
+        assert!(matches!(error, Error::InvalidInterpreter(0x201)));
+    }
+
+    #[test]
+    fn test_ram_page() {
+        let font = Font::default();
+        let mut vip = Vip::new(&[]);
+
+        for (size, page) in [(0x1000, 0x0F00), (0x80, 0x0000), (0x1000000, 0x7F00)] {
+            let map = MemoryMap {
+                size,
+                ..MemoryMap::COSMAC_VIP
+            };
+            vip.reset(&mut Memory::with_map(&map, &font), &font);
+            assert_eq!(vip.cpu().r[1], page);
+        }
+    }
+
+    #[test]
+    fn test_stopped_machine() {
+        let mut machine = Machine::with_config(Config::for_profile(Profile::Chip8E));
+        machine.load_rom(&[0x00, 0xED]).unwrap();
+        assert!(!machine.step().unwrap());
+
+        machine.set_vip(Some(Vip::new(&[0x30, 0x00]))).unwrap();
+        assert!(!machine.step_frame().unwrap());
+        assert_eq!(machine.get_vip().unwrap().cpu().r[0], 0x0000);
+    }
+}
@@ -0,0 +1,383 @@
+// RCA CDP1802 CPU. sixteen 16-bit registers, any of which can be the
+// program counter (P) or the data pointer (X), and an 8-bit accumulator D.
+// every instruction takes two machine cycles of 8 clocks, long branches
+// and skips take three.
+
+// memory and IO seen by the CPU
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+    // OUT 1-7 and INP 1-7, N0-N2 select the device
+    fn output(&mut self, port: u8, value: u8);
+    fn input(&mut self, port: u8) -> u8;
+    // state of the external flag lines EF1-EF4, true when asserted
+    fn flag(&self, line: u8) -> bool;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub r: [u16; 16],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    // X and P saved by an interrupt
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    // stopped by IDL until the next DMA or interrupt
+    pub idle: bool,
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    // the reset line clears P, X, R0 and Q and enables interrupts,
+    // the other registers keep their value
+    pub fn reset(&mut self) {
+        self.p = 0;
+        self.x = 0;
+        self.r[0] = 0;
+        self.q = false;
+        self.ie = true;
+        self.idle = false;
+    }
+
+    // responds to the interrupt line, returns false while interrupts are
+    // disabled
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+
+        self.t = self.x << 4 | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    // one DMA out cycle, the byte at R0 is handed to the device
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    // executes one instruction, returns the machine cycles it took
+    pub fn step(&mut self, bus: &mut impl Bus) -> u8 {
+        if self.idle {
+            return 2;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.r[n]),
+            0x1 => self.r[n] = self.r[n].wrapping_add(1),
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1),
+            0x3 => {
+                let taken = match n {
+                    0x8 => {
+                        // SKP
+                        self.fetch(bus);
+                        return 2;
+                    }
+                    _ => self.condition(bus, n as u8),
+                };
+                // the page is the one of the target byte
+                let page = self.r[self.p as usize] & 0xFF00;
+                let target = self.fetch(bus);
+                if taken {
+                    self.r[self.p as usize] = page | target as u16;
+                }
+            }
+            0x4 => {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => bus.write(self.r[n], self.d),
+            0x6 => match n {
+                0x0 => self.r[x] = self.r[x].wrapping_add(1),
+                0x1..=0x7 => {
+                    let value = bus.read(self.r[x]);
+                    bus.output(n as u8, value);
+                    self.r[x] = self.r[x].wrapping_add(1);
+                }
+                // 68 is unused on the 1802
+                0x8 => {}
+                _ => {
+                    self.d = bus.input(n as u8 - 8);
+                    bus.write(self.r[x], self.d);
+                }
+            },
+            0x7 => self.op_7(bus, n as u8),
+            0x8 => self.d = self.r[n] as u8,
+            0x9 => self.d = (self.r[n] >> 8) as u8,
+            0xA => self.r[n] = self.r[n] & 0xFF00 | self.d as u16,
+            0xB => self.r[n] = self.r[n] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.op_long(bus, n as u8);
+                return 3;
+            }
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+            _ => self.op_f(bus, n as u8),
+        }
+
+        2
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let pc = &mut self.r[self.p as usize];
+        let value = bus.read(*pc);
+        *pc = pc.wrapping_add(1);
+        value
+    }
+
+    // condition of the short branch 3N and long branch CN families,
+    // N8-NF test the opposite of N0-N7
+    fn condition(&self, bus: &impl Bus, n: u8) -> bool {
+        let value = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            line => bus.flag(line - 3),
+        };
+        value != (n & 0x8 != 0)
+    }
+
+    fn op_7(&mut self, bus: &mut impl Bus, n: u8) {
+        let x = self.x as usize;
+
+        match n {
+            // RET, DIS
+            0x0 | 0x1 => {
+                let value = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0x0;
+            }
+            // LDXA
+            0x2 => {
+                self.d = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            // STXD
+            0x3 => {
+                bus.write(self.r[x], self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            // SHRC
+            0x6 => {
+                let carry = self.d & 1 != 0;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            // SHLC
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+            // SAV
+            0x8 => bus.write(self.r[x], self.t),
+            // MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            // ADC, SDB, SMB and their immediate forms
+            _ => {
+                let operand = match n {
+                    0x4 | 0x5 | 0x7 => bus.read(self.r[x]),
+                    _ => self.fetch(bus),
+                };
+                self.arithmetic(n & 0x3, operand, self.df);
+            }
+        }
+    }
+
+    fn op_long(&mut self, bus: &mut impl Bus, n: u8) {
+        let pc = self.p as usize;
+
+        match n {
+            // NOP
+            0x4 => {}
+            // LSNQ, LSNZ, LSNF, LSKP, LSIE, LSQ, LSZ, LSDF
+            0x5..=0x8 | 0xC..=0xF => {
+                let skip = match n {
+                    0x5 => !self.q,
+                    0x6 => self.d != 0,
+                    0x7 => !self.df,
+                    0x8 => true,
+                    0xC => self.ie,
+                    0xD => self.q,
+                    0xE => self.d == 0,
+                    _ => self.df,
+                };
+                if skip {
+                    self.r[pc] = self.r[pc].wrapping_add(2);
+                }
+            }
+            // LBR, LBQ, LBZ, LBDF and the negated LBNQ, LBNZ, LBNF
+            _ => {
+                let high = self.fetch(bus);
+                let low = self.fetch(bus);
+                if self.condition(bus, n) {
+                    self.r[pc] = u16::from_be_bytes([high, low]);
+                }
+            }
+        }
+    }
+
+    fn op_f(&mut self, bus: &mut impl Bus, n: u8) {
+        let x = self.x as usize;
+
+        match n {
+            0x6 => {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            }
+            0xE => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => {
+                // F0-F7 work on M(RX), F8-FF on the immediate byte
+                let operand = match n {
+                    0x0..=0x7 => bus.read(self.r[x]),
+                    _ => self.fetch(bus),
+                };
+                match n & 0x7 {
+                    0x0 => self.d = operand,
+                    0x1 => self.d |= operand,
+                    0x2 => self.d &= operand,
+                    0x3 => self.d ^= operand,
+                    // ADD, SD, SM without carry in
+                    op => self.arithmetic(op & 0x3, operand, op != 0x4),
+                }
+            }
+        }
+    }
+
+    // 0 and 4 add, 1 and 5 compute M - D, 3 and 7 D - M. the carry in is
+    // DF for the with-carry forms and 1 or 0 otherwise, DF is set on carry
+    // and cleared on borrow.
+    fn arithmetic(&mut self, op: u8, operand: u8, carry: bool) {
+        let (result, df) = match op {
+            0x0 => {
+                let sum = self.d as u16 + operand as u16 + carry as u16;
+                (sum as u8, sum > 0xFF)
+            }
+            0x1 => Self::subtract(operand, self.d, carry),
+            _ => Self::subtract(self.d, operand, carry),
+        };
+        self.d = result;
+        self.df = df;
+    }
+
+    fn subtract(minuend: u8, subtrahend: u8, no_borrow: bool) -> (u8, bool) {
+        let difference = minuend as u16 + (!subtrahend) as u16 + no_borrow as u16;
+        (difference as u8, difference > 0xFF)
+    }
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bus, Cdp1802};
+
+    struct Ram {
+        data: [u8; 256],
+        output: Vec<(u8, u8)>,
+    }
+
+    impl Bus for Ram {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.data[addr as usize & 0xFF]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.data[addr as usize & 0xFF] = value;
+        }
+
+        fn output(&mut self, port: u8, value: u8) {
+            self.output.push((port, value));
+        }
+
+        fn input(&mut self, _: u8) -> u8 {
+            0
+        }
+
+        fn flag(&self, _: u8) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut ram = Ram {
+            data: [0; 256],
+            output: Vec::new(),
+        };
+        let code = [
+            0xF8, 0x80, 0xA3, // R3 = 0x80
+            0xF8, 0x05, 0x53, // M(R3) = 5
+            0xE3, // X = 3
+            0xF8, 0x07, 0xF7, // D = 7 - 5
+            0xFE, // D = 4
+            0xF5, // D = 5 - 4
+            0x3B, 0x20, // not taken, DF is set
+            0x7B, // Q = 1
+            0x64, // OUT 4
+            0xC0, 0x00, 0x18, // LBR 0x18
+        ];
+        ram.data[..code.len()].copy_from_slice(&code);
+
+        let mut cpu = Cdp1802::new();
+        let mut cycles = 0;
+        while !cpu.idle {
+            cycles += cpu.step(&mut ram) as u32;
+        }
+
+        assert_eq!(cycles, 29);
+        assert_eq!((cpu.d, cpu.df, cpu.q), (1, true, true));
+        assert_eq!(cpu.r[0], 0x19);
+        assert_eq!(cpu.r[3], 0x81);
+        assert_eq!(ram.output, [(4, 5)]);
+
+        // interrupts save X and P in T and wake the CPU
+        assert!(cpu.interrupt());
+        assert_eq!((cpu.t, cpu.x, cpu.p, cpu.ie), (0x30, 2, 1, false));
+        assert!(!cpu.interrupt());
+    }
+}
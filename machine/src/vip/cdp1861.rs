@@ -0,0 +1,72 @@
+// RCA CDP1861 video chip. a frame is 262 lines of 14 machine cycles. while
+// the display is on the chip interrupts the CPU two lines before the 128
+// visible ones, then takes 8 bytes by DMA at the start of every visible
+// line, leaving 6 machine cycles of it to the CPU. EF1 is asserted during
+// the 4 lines before the visible area and the last 4 lines of it.
+
+pub const LINES: u16 = 262;
+pub const LINE_CYCLES: u8 = 14;
+pub const VISIBLE_LINES: u16 = 128;
+// bytes taken by DMA per visible line, 64 pixels
+pub const LINE_BYTES: u8 = 8;
+
+const FIRST_VISIBLE: u16 = 80;
+const INTERRUPT_LINES: std::ops::Range<u16> = FIRST_VISIBLE - 2..FIRST_VISIBLE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cdp1861 {
+    enabled: bool,
+    line: u16,
+}
+
+impl Cdp1861 {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            line: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // INP 1 turns the display on, OUT 1 off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn line(&self) -> u16 {
+        self.line
+    }
+
+    pub fn advance(&mut self) {
+        self.line = (self.line + 1) % LINES;
+    }
+
+    // state of the interrupt line during the current line
+    pub fn interrupt(&self) -> bool {
+        self.enabled && INTERRUPT_LINES.contains(&self.line)
+    }
+
+    // state of EF1 during the current line
+    pub fn flag(&self) -> bool {
+        let last = FIRST_VISIBLE + VISIBLE_LINES;
+        self.enabled
+            && ((FIRST_VISIBLE - 4..FIRST_VISIBLE).contains(&self.line)
+                || (last - 4..last).contains(&self.line))
+    }
+
+    // index of the current line in the visible area, None while nothing
+    // is fetched by DMA
+    pub fn visible_line(&self) -> Option<u16> {
+        let line = self.line.checked_sub(FIRST_VISIBLE)?;
+        (self.enabled && line < VISIBLE_LINES).then_some(line)
+    }
+}
+
+impl Default for Cdp1861 {
+    fn default() -> Self {
+        Self::new()
+    }
+}